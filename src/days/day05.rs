@@ -1,6 +1,16 @@
+use anyhow::anyhow;
 use itertools::Itertools;
 use lazy_static::lazy_static;
+use miette::{GraphicalReportHandler, SourceSpan};
+use nom::Offset;
 use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
 pub fn day05() {
     let input = include_str!("../../input/05.txt");
@@ -9,8 +19,141 @@ pub fn day05() {
     day05b(input);
 }
 
+/// Replays the crane moves of the puzzle input in the terminal from step `start` on, waiting
+/// `delay` between frames.
+pub fn day05_replay(delay: Duration, start: usize) {
+    let input = include_str!("../../input/05.txt");
+    let result = parse_input(input).and_then(|(towers, steps)| {
+        let mut replay = Replay::new(towers, steps, CrateMover9001);
+        replay.jump_to(start)?;
+        replay.play(delay)
+    });
+    if let Err(e) = result {
+        report(e);
+    }
+}
+
+/// Steps through the crane moves of the puzzle input with commands read from stdin, starting at
+/// step `start`.
+pub fn day05_browse(start: usize) {
+    let input = include_str!("../../input/05.txt");
+    let result = parse_input(input).and_then(|(towers, steps)| {
+        let mut replay = Replay::new(towers, steps, CrateMover9001);
+        replay.jump_to(start)?;
+        Ok(replay)
+    });
+    match result {
+        Ok(mut replay) => {
+            if let Err(e) = replay.browse(io::stdin().lock(), io::stdout(), true) {
+                println!("{e}");
+            }
+        }
+        Err(e) => report(e),
+    }
+}
+
+const REPLAY_HELP: &str = "Commands:
+  next, n, <enter>  perform the next step
+  previous, p       undo the last step
+  goto, g <n>       jump to step n
+  quit, q           stop browsing";
+
+/// Writes every frame of the crane moves of the puzzle input to `path`.
+pub fn day05_dump(path: &Path) {
+    let input = include_str!("../../input/05.txt");
+    let result = parse_input(input)
+        .map_err(anyhow::Error::from)
+        .and_then(|(towers, steps)| Replay::new(towers, steps, CrateMover9001).dump(path));
+    if let Err(e) = result {
+        println!("{e}");
+    }
+}
+
+/// Prints the final arrangement of the crates in the input format, so it can be fed back in.
+pub fn day05_final_arrangement() {
+    let input = include_str!("../../input/05.txt");
+    match rearrange(input, &CrateMover9001) {
+        Ok(towers) => println!("{towers}"),
+        Err(e) => report(e),
+    }
+}
+
+/// Searches instructions turning the drawing in the file `start` into the one in `goal` and
+/// prints them.
+pub fn day05_plan(start: &Path, goal: &Path, max_moves: usize) {
+    let read = |path: &Path| -> anyhow::Result<Towers> {
+        let drawing = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read {}: {e}", path.display()))?;
+        let drawing = parse_drawing(drawing.trim_end_matches('\n'))
+            .map_err(|e| anyhow!("Malformed drawing in {}: {e}", path.display()))?;
+        Ok(drawing.towers)
+    };
+    let result = read(start).and_then(|start| {
+        let goal = read(goal)?;
+        Ok(solve(&start, &goal, &CrateMover9001, max_moves)?)
+    });
+    match result {
+        Ok(instructions) => println!("{}", instructions.iter().join("\n")),
+        Err(e) => println!("{e}"),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Towers(Vec<Vec<char>>);
 
+impl std::fmt::Display for Towers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let labels = (1..=self.0.len()).map(|i| i.to_string()).collect_vec();
+        write_drawing(f, self, &labels)
+    }
+}
+
+/// A tower drawing as found in the puzzle input, including its column labels.
+#[derive(Debug, PartialEq, Eq)]
+struct Drawing {
+    labels: Vec<String>,
+    towers: Towers,
+}
+
+impl std::fmt::Display for Drawing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_drawing(f, &self.towers, &self.labels)
+    }
+}
+
+/// Writes the towers in the input format. Every column is as wide as its widest label, but at
+/// least as wide as a crate, and columns are separated by a single space.
+fn write_drawing(
+    f: &mut std::fmt::Formatter<'_>,
+    towers: &Towers,
+    labels: &[String],
+) -> std::fmt::Result {
+    let width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(3);
+    let longest = towers.0.iter().map(|tower| tower.len()).max().unwrap_or(0);
+
+    for j in (0..longest).rev() {
+        let row = towers
+            .0
+            .iter()
+            .map(|tower| match tower.get(j) {
+                Some(c) => format!("{:^width$}", format!("[{c}]")),
+                None => " ".repeat(width),
+            })
+            .join(" ");
+        writeln!(f, "{row}")?;
+    }
+    let labels = labels
+        .iter()
+        .map(|label| format!("{label:^width$}"))
+        .join(" ");
+    f.write_str(&labels)
+}
+
 impl std::fmt::Debug for Towers {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let num_towers = self.0.len();
@@ -33,86 +176,392 @@ impl std::fmt::Debug for Towers {
 }
 
 fn day05a(input: &str) {
-    let (towers, instructions) = input
-        .split_once("\n\n")
-        .expect("Input for tower and instructions is not separated by empty line");
-
-    let initial = parse_towers_setup(towers);
-    let end = instructions
-        .lines()
-        .filter_map(parse_instruction)
-        .fold(initial, |mut acc, x| {
-            acc.perform_move_a(x);
-            acc
-        });
-    let tops = end.tops();
-    println!("Day05a: {tops}");
+    match rearrange(input, &CrateMover9000) {
+        Ok(towers) => println!("Day05a: {}", towers.tops()),
+        Err(e) => report(e),
+    }
 }
 
 fn day05b(input: &str) {
-    let (towers, instructions) = input
-        .split_once("\n\n")
-        .expect("Input for tower and instructions is not separated by empty line");
+    match rearrange(input, &CrateMover9001) {
+        Ok(towers) => println!("Day05b: {}", towers.tops()),
+        Err(e) => report(e),
+    }
+}
+
+fn report(err: InstructionError) {
+    let mut s = String::new();
+    GraphicalReportHandler::new()
+        .render_report(&mut s, &err)
+        .unwrap();
+    println!("{s}");
+}
+
+fn rearrange(input: &str, crane: &impl Crane) -> Result<Towers, InstructionError> {
+    let (initial, steps) = parse_input(input)?;
+    steps.iter().try_fold(initial, |mut acc, step| {
+        acc.perform_move(crane, step)?;
+        Ok(acc)
+    })
+}
 
-    let initial = parse_towers_setup(towers);
-    let end = instructions
+fn parse_input(input: &str) -> Result<(Towers, Vec<Step<'_>>), InstructionError> {
+    let (towers, instructions) =
+        input
+            .split_once("\n\n")
+            .ok_or_else(|| InstructionError::MissingSeparator {
+                src: input.to_string(),
+                span: (0, input.len()).into(),
+            })?;
+    let towers = parse_drawing(towers)
+        .map_err(|e| InstructionError::MalformedDrawing {
+            reason: e.to_string(),
+            src: input.to_string(),
+            span: (0, towers.len()).into(),
+        })?
+        .towers;
+
+    let steps = instructions
         .lines()
-        .filter_map(parse_instruction)
-        .fold(initial, |mut acc, x| {
-            acc.perform_move_b(x);
-            acc
-        });
-    let tops = end.tops();
-    println!("Day05b: {tops}");
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_instruction(input, line))
+        .collect::<Result<_, _>>()?;
+    Ok((towers, steps))
 }
 
-fn parse_towers_setup(towers: &str) -> Towers {
-    let lines: Vec<_> = towers.lines().rev().collect();
-    let numberings = lines[0];
-    let num_towers = (numberings.len()) / 4 + 1;
-    let mut towers = vec![Vec::new(); num_towers];
+/// A crane model deciding in which order crates end up on the target tower.
+trait Crane {
+    /// Takes `amount` crates off `tower` and returns them in the order they are put down.
+    fn pick_up(&self, tower: &mut Vec<char>, amount: usize) -> Vec<char>;
 
-    for line in lines.iter().skip(1) {
-        for (i, cr) in (&line.chars().chunks(4)).into_iter().enumerate() {
-            let cr: String = cr.collect();
-            let crate_char = cr.chars().nth(1).unwrap();
-            if crate_char.is_whitespace() {
-                continue;
+    /// Whether crates are only ever taken off the top, so the crates below stay where they are.
+    fn takes_from_top(&self) -> bool {
+        true
+    }
+}
+
+/// Moves one crate at a time, so the moved crates end up reversed.
+struct CrateMover9000;
+
+/// Moves all crates at once, keeping their order.
+struct CrateMover9001;
+
+/// Moves at most `capacity` crates at once, splitting larger moves into chunks.
+struct LimitedCrane {
+    capacity: usize,
+}
+
+/// Pulls crates out from the bottom of the tower instead of the top.
+struct BottomCrane;
+
+impl Crane for CrateMover9000 {
+    fn pick_up(&self, tower: &mut Vec<char>, amount: usize) -> Vec<char> {
+        let top = tower.len();
+        tower.drain((top - amount)..).rev().collect()
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn pick_up(&self, tower: &mut Vec<char>, amount: usize) -> Vec<char> {
+        let top = tower.len();
+        tower.drain((top - amount)..).collect()
+    }
+}
+
+impl Crane for LimitedCrane {
+    fn pick_up(&self, tower: &mut Vec<char>, amount: usize) -> Vec<char> {
+        let capacity = self.capacity.max(1);
+        let mut buffer = Vec::with_capacity(amount);
+        let mut remaining = amount;
+        while remaining > 0 {
+            let chunk = remaining.min(capacity);
+            buffer.append(&mut CrateMover9001.pick_up(tower, chunk));
+            remaining -= chunk;
+        }
+        buffer
+    }
+}
+
+impl Crane for BottomCrane {
+    fn pick_up(&self, tower: &mut Vec<char>, amount: usize) -> Vec<char> {
+        tower.drain(..amount).collect()
+    }
+
+    fn takes_from_top(&self) -> bool {
+        false
+    }
+}
+
+/// Parses a tower drawing. The columns are taken from the label line, and every crate belongs to
+/// the label closest to it, so labels may be wider than a crate and lines may be ragged.
+fn parse_drawing(drawing: &str) -> anyhow::Result<Drawing> {
+    let mut lines = drawing.lines().filter(|line| !line.trim().is_empty()).rev();
+    let label_line = lines.next().ok_or(anyhow!("Empty tower drawing"))?;
+
+    // Doubled centers of the labels, to stay in integers
+    let mut labels = vec![];
+    let mut centers = vec![];
+    for (start, label) in tokens(label_line) {
+        centers.push(2 * start + label.chars().count() - 1);
+        labels.push(label);
+    }
+
+    let mut towers = vec![Vec::new(); labels.len()];
+    for (row, line) in lines.enumerate() {
+        let chars = line.chars().collect_vec();
+        let mut j = 0;
+        while j < chars.len() {
+            match chars[j..] {
+                ['[', c, ']', ..] => {
+                    let center = 2 * (j + 1);
+                    let column = (0..centers.len())
+                        .min_by_key(|&i| centers[i].abs_diff(center))
+                        .unwrap();
+                    if towers[column].len() != row {
+                        return Err(anyhow!(
+                            "Crate [{c}] in line {line:?} does not sit on top of tower {}",
+                            labels[column]
+                        ));
+                    }
+                    towers[column].push(c);
+                    j += 3;
+                }
+                [c, ..] if c.is_whitespace() => j += 1,
+                [c, ..] => return Err(anyhow!("Unexpected character {c:?} in line {line:?}")),
+                [] => unreachable!(),
             }
-            towers[i].push(crate_char);
         }
     }
 
-    Towers(towers)
+    Ok(Drawing {
+        labels,
+        towers: Towers(towers),
+    })
+}
+
+/// Splits `line` at whitespace, yielding every part with the char index it starts at.
+fn tokens(line: &str) -> Vec<(usize, String)> {
+    line.chars()
+        .enumerate()
+        .group_by(|(_, c)| c.is_whitespace())
+        .into_iter()
+        .filter(|(is_whitespace, _)| !is_whitespace)
+        .map(|(_, mut group)| {
+            let (start, first) = group.next().unwrap();
+            let token = std::iter::once(first)
+                .chain(group.map(|(_, c)| c))
+                .collect();
+            (start, token)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    amount: usize,
+    from: usize,
+    to: usize,
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.amount,
+            self.from + 1,
+            self.to + 1
+        )
+    }
+}
+
+/// An instruction together with where its parts are located in the puzzle input.
+#[derive(Debug)]
+struct Step<'a> {
+    src: &'a str,
+    line: &'a str,
+    instruction: Instruction,
+    amount_span: SourceSpan,
+    from_span: SourceSpan,
+    to_span: SourceSpan,
+}
+
+#[derive(thiserror::Error, Debug, miette::Diagnostic)]
+enum InstructionError {
+    #[error("the tower drawing is not followed by an empty line")]
+    MissingSeparator {
+        #[source_code]
+        src: String,
+        #[label("expected an empty line between the drawing and the instructions")]
+        span: SourceSpan,
+    },
+
+    #[error("malformed tower drawing: {reason}")]
+    MalformedDrawing {
+        reason: String,
+        #[source_code]
+        src: String,
+        #[label("in this drawing")]
+        span: SourceSpan,
+    },
+
+    #[error("malformed instruction `{instruction}`")]
+    Malformed {
+        instruction: String,
+        #[source_code]
+        src: String,
+        #[label("expected `move <amount> from <tower> to <tower>`")]
+        span: SourceSpan,
+    },
+
+    #[error("tower 0 does not exist in `{instruction}`")]
+    ZeroTower {
+        instruction: String,
+        #[source_code]
+        src: String,
+        #[label("towers are numbered starting at 1")]
+        span: SourceSpan,
+    },
+
+    #[error("tower {tower} does not exist in `{instruction}`")]
+    UnknownTower {
+        instruction: String,
+        tower: usize,
+        count: usize,
+        #[source_code]
+        src: String,
+        #[label("there are only {count} towers")]
+        span: SourceSpan,
+        #[help]
+        towers: String,
+    },
+
+    #[error("cannot move {amount} crates from tower {tower} in `{instruction}`")]
+    NotEnoughCrates {
+        instruction: String,
+        amount: usize,
+        tower: usize,
+        available: usize,
+        #[source_code]
+        src: String,
+        #[label("tower {tower} only holds {available} crates")]
+        span: SourceSpan,
+        #[help]
+        towers: String,
+    },
 }
 
-fn parse_instruction(instruction: &str) -> Option<(usize, usize, usize)> {
+/// Parses a single instruction `line`, which has to be a slice of `src`.
+fn parse_instruction<'a>(src: &'a str, line: &'a str) -> Result<Step<'a>, InstructionError> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"move (\d+) from (\d+) to (\d+)").unwrap();
+        static ref RE: Regex = Regex::new(r"^\s*move (\d+) from (\d+) to (\d+)\s*$").unwrap();
     }
-    let captures = RE.captures(instruction)?;
-    Some((
-        captures[1].parse::<usize>().ok()?,
-        captures[2].parse::<usize>().ok()? - 1usize,
-        captures[3].parse::<usize>().ok()? - 1usize,
-    ))
+    let offset = src.offset(line);
+    let captures = RE
+        .captures(line)
+        .ok_or_else(|| InstructionError::Malformed {
+            instruction: line.to_string(),
+            src: src.to_string(),
+            span: (offset, line.len()).into(),
+        })?;
+
+    let mut spans = [SourceSpan::from(0); 3];
+    let mut numbers = [0; 3];
+    for i in 0..3 {
+        let capture = captures.get(i + 1).unwrap();
+        spans[i] = (offset + capture.start(), capture.as_str().len()).into();
+        numbers[i] =
+            capture
+                .as_str()
+                .parse::<usize>()
+                .map_err(|_| InstructionError::Malformed {
+                    instruction: line.to_string(),
+                    src: src.to_string(),
+                    span: spans[i],
+                })?;
+    }
+
+    for i in 1..3 {
+        if numbers[i] == 0 {
+            return Err(InstructionError::ZeroTower {
+                instruction: line.to_string(),
+                src: src.to_string(),
+                span: spans[i],
+            });
+        }
+    }
+
+    Ok(Step {
+        src,
+        line,
+        instruction: Instruction {
+            amount: numbers[0],
+            from: numbers[1] - 1,
+            to: numbers[2] - 1,
+        },
+        amount_span: spans[0],
+        from_span: spans[1],
+        to_span: spans[2],
+    })
 }
 
 impl Towers {
-    fn perform_move_a(&mut self, (amount, from, to): (usize, usize, usize)) {
-        let top = self.0[from].len();
-        let range = (top - amount)..;
-        let mut buffer: Vec<_> = self.0[from].drain(range).rev().collect();
-        self.0[to].append(&mut buffer);
+    fn perform_move(&mut self, crane: &impl Crane, step: &Step) -> Result<(), InstructionError> {
+        self.check(step)?;
+        self.apply(crane, step.instruction);
+        Ok(())
     }
 
-    fn perform_move_b(&mut self, (amount, from, to): (usize, usize, usize)) {
-        let top = self.0[from].len();
-        let range = (top - amount)..;
-        let mut buffer: Vec<_> = self.0[from].drain(range).collect();
+    /// Performs `instruction` without validating it first.
+    fn apply(&mut self, crane: &impl Crane, Instruction { amount, from, to }: Instruction) {
+        let mut buffer = crane.pick_up(&mut self.0[from], amount);
         self.0[to].append(&mut buffer);
     }
 
+    /// All instructions that can be performed on the current state.
+    fn possible_instructions(&self) -> impl Iterator<Item = Instruction> + '_ {
+        let count = self.0.len();
+        (0..count)
+            .cartesian_product(0..count)
+            .filter(|(from, to)| from != to)
+            .flat_map(move |(from, to)| {
+                (1..=self.0[from].len()).map(move |amount| Instruction { amount, from, to })
+            })
+    }
+
+    /// Verifies that `step` can be performed on the current state of the towers.
+    fn check(&self, step: &Step) -> Result<(), InstructionError> {
+        let Instruction { amount, from, to } = step.instruction;
+        let count = self.0.len();
+        for (tower, span) in [(from, step.from_span), (to, step.to_span)] {
+            if tower >= count {
+                return Err(InstructionError::UnknownTower {
+                    instruction: step.line.to_string(),
+                    tower: tower + 1,
+                    count,
+                    src: step.src.to_string(),
+                    span,
+                    towers: format!("towers before this step:{self:?}"),
+                });
+            }
+        }
+
+        let available = self.0[from].len();
+        if amount > available {
+            return Err(InstructionError::NotEnoughCrates {
+                instruction: step.line.to_string(),
+                amount,
+                tower: from + 1,
+                available,
+                src: step.src.to_string(),
+                span: step.amount_span,
+                towers: format!("towers before this step:{self:?}"),
+            });
+        }
+
+        Ok(())
+    }
+
     fn tops(&self) -> String {
         self.0
             .iter()
@@ -121,3 +570,666 @@ impl Towers {
             .join("")
     }
 }
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum PlanError {
+    #[error("the start has {start} towers, but the goal has {goal}")]
+    TowerCount { start: usize, goal: usize },
+    #[error("the goal does not hold the same crates as the start")]
+    DifferentCrates,
+    #[error("the goal cannot be reached with this crane")]
+    Unreachable,
+    #[error("the goal cannot be reached within {0} moves")]
+    TooManyMoves(usize),
+    #[error("gave up after looking at {0} arrangements")]
+    TooManyArrangements(usize),
+}
+
+/// Most arrangements [`solve`] stores before giving up.
+const MAX_PLAN_ARRANGEMENTS: usize = 200_000;
+
+/// Finds a shortest list of instructions that rearranges `start` into `goal` with `crane`,
+/// storing at most [`MAX_PLAN_ARRANGEMENTS`] arrangements.
+fn solve(
+    start: &Towers,
+    goal: &Towers,
+    crane: &impl Crane,
+    max_moves: usize,
+) -> Result<Vec<Instruction>, PlanError> {
+    solve_within(start, goal, crane, max_moves, MAX_PLAN_ARRANGEMENTS)
+}
+
+/// An arrangement found by [`solve_within`], with the fewest moves it is known to be reached in,
+/// and the index of its predecessor and the instruction leading from there.
+struct PlanNode {
+    towers: Rc<Towers>,
+    moves: usize,
+    parent: Option<(usize, Instruction)>,
+}
+
+/// This is an A* search over the arrangements of at most `max_moves` instructions, guided by
+/// [`moves_needed`]. Every arrangement is stored once, shared between the lookup by arrangement
+/// and the list of nodes, and the search gives up once `max_arrangements` are stored.
+fn solve_within(
+    start: &Towers,
+    goal: &Towers,
+    crane: &impl Crane,
+    max_moves: usize,
+    max_arrangements: usize,
+) -> Result<Vec<Instruction>, PlanError> {
+    if start.0.len() != goal.0.len() {
+        return Err(PlanError::TowerCount {
+            start: start.0.len(),
+            goal: goal.0.len(),
+        });
+    }
+    let crates = |towers: &Towers| towers.0.iter().flatten().copied().sorted().collect_vec();
+    if crates(start) != crates(goal) {
+        return Err(PlanError::DifferentCrates);
+    }
+
+    let start = Rc::new(start.clone());
+    let mut lookup = HashMap::from([(start.clone(), 0)]);
+    let mut nodes = vec![PlanNode {
+        towers: start,
+        moves: 0,
+        parent: None,
+    }];
+    // Ordered by the fewest moves an arrangement could lead to the goal in, then by the most
+    // moves already made
+    let mut queue = BinaryHeap::from([Reverse((0, Reverse(0), 0))]);
+    // Whether arrangements were left out for being too far away
+    let mut cut_off = false;
+
+    while let Some(Reverse((_, Reverse(moves), index))) = queue.pop() {
+        if moves > nodes[index].moves {
+            // Reached in fewer moves since this was queued
+            continue;
+        }
+        let towers = nodes[index].towers.clone();
+        if *towers == *goal {
+            let mut instructions = vec![];
+            let mut current = index;
+            while let Some((parent, instruction)) = nodes[current].parent {
+                instructions.push(instruction);
+                current = parent;
+            }
+            instructions.reverse();
+            return Ok(instructions);
+        }
+
+        for instruction in towers.possible_instructions() {
+            let mut next = (*towers).clone();
+            next.apply(crane, instruction);
+            let estimate = moves + 1 + moves_needed(&next, goal, crane);
+            if estimate > max_moves {
+                cut_off = true;
+                continue;
+            }
+            let next = match lookup.get(&next) {
+                Some(&known) if nodes[known].moves <= moves + 1 => continue,
+                Some(&known) => known,
+                None if nodes.len() == max_arrangements => {
+                    return Err(PlanError::TooManyArrangements(max_arrangements))
+                }
+                None => {
+                    let next = Rc::new(next);
+                    lookup.insert(next.clone(), nodes.len());
+                    nodes.push(PlanNode {
+                        towers: next,
+                        moves: 0,
+                        parent: None,
+                    });
+                    nodes.len() - 1
+                }
+            };
+            nodes[next].moves = moves + 1;
+            nodes[next].parent = Some((index, instruction));
+            queue.push(Reverse((estimate, Reverse(moves + 1), next)));
+        }
+    }
+
+    Err(if cut_off {
+        PlanError::TooManyMoves(max_moves)
+    } else {
+        PlanError::Unreachable
+    })
+}
+
+/// A lower bound for the moves from `towers` to `goal` with `crane`. Every tower holding crates
+/// that have to leave it needs a move taking from it, and every tower missing crates needs a move
+/// putting onto it. A move takes from and puts onto a single tower, and changes whether crates
+/// have to leave or are missing for at most one tower each, so the bound is consistent.
+///
+/// If the crane takes from the top, only the crates matching the bottom of the goal tower can
+/// stay. Otherwise only the crates the goal tower holds in any order are known to stay.
+fn moves_needed(towers: &Towers, goal: &Towers, crane: &impl Crane) -> usize {
+    let (mut leaving, mut missing) = (0, 0);
+    for (tower, goal) in towers.0.iter().zip(&goal.0) {
+        let staying = if crane.takes_from_top() {
+            tower.iter().zip(goal).take_while(|(c, g)| c == g).count()
+        } else {
+            let mut rest = goal.clone();
+            tower
+                .iter()
+                .filter(|c| match rest.iter().position(|g| g == *c) {
+                    Some(i) => {
+                        rest.swap_remove(i);
+                        true
+                    }
+                    None => false,
+                })
+                .count()
+        };
+        leaving += usize::from(tower.len() > staying);
+        missing += usize::from(goal.len() > staying);
+    }
+    leaving.max(missing)
+}
+
+/// Runs a list of steps one at a time and keeps an undo log, so it can also go backwards.
+struct Replay<'a, C: Crane> {
+    towers: Towers,
+    steps: Vec<Step<'a>>,
+    crane: C,
+    /// The `from` and `to` towers as they were before each performed step.
+    undo_log: Vec<[(usize, Vec<char>); 2]>,
+}
+
+impl<'a, C: Crane> Replay<'a, C> {
+    fn new(towers: Towers, steps: Vec<Step<'a>>, crane: C) -> Self {
+        Replay {
+            towers,
+            steps,
+            crane,
+            undo_log: vec![],
+        }
+    }
+
+    /// Number of steps performed so far.
+    fn position(&self) -> usize {
+        self.undo_log.len()
+    }
+
+    /// Performs the next step. Returns `false` if all steps are already performed.
+    fn step_forward(&mut self) -> Result<bool, InstructionError> {
+        let Some(step) = self.steps.get(self.position()) else {
+            return Ok(false);
+        };
+        let Instruction { from, to, .. } = step.instruction;
+        self.towers.check(step)?;
+        let snapshot = [
+            (from, self.towers.0[from].clone()),
+            (to, self.towers.0[to].clone()),
+        ];
+        self.towers.apply(&self.crane, step.instruction);
+        self.undo_log.push(snapshot);
+        Ok(true)
+    }
+
+    /// Reverts the last performed step. Returns `false` if no step was performed yet.
+    fn step_back(&mut self) -> bool {
+        match self.undo_log.pop() {
+            Some(snapshot) => {
+                // Restore in reverse, so `to` comes before `from` when both are the same tower
+                for (tower, crates) in snapshot.into_iter().rev() {
+                    self.towers.0[tower] = crates;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves forwards or backwards until exactly `n` steps are performed.
+    fn jump_to(&mut self, n: usize) -> Result<(), InstructionError> {
+        let n = n.min(self.steps.len());
+        while self.position() > n {
+            self.step_back();
+        }
+        while self.position() < n {
+            self.step_forward()?;
+        }
+        Ok(())
+    }
+
+    fn frame(&self) -> String {
+        let position = self.position();
+        let title = match position {
+            0 => "start".to_string(),
+            n => self.steps[n - 1].line.to_string(),
+        };
+        format!(
+            "Step {position}/{}: {title}{:?}",
+            self.steps.len(),
+            self.towers
+        )
+    }
+
+    /// Renders every remaining frame into the terminal, waiting `delay` in between.
+    fn play(&mut self, delay: Duration) -> Result<(), InstructionError> {
+        loop {
+            println!("\x1b[2J\x1b[H{}", self.frame());
+            std::io::stdout().flush().unwrap();
+            if !self.step_forward()? {
+                return Ok(());
+            }
+            thread::sleep(delay);
+        }
+    }
+
+    /// Executes every line of `input` as a navigation command and writes the resulting frames to
+    /// `output`, starting with the current one. A step that cannot be performed is reported, and
+    /// the replay stays where it is.
+    fn browse(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+        prompt: bool,
+    ) -> io::Result<()> {
+        writeln!(output, "{}", self.frame())?;
+        if prompt {
+            write!(output, "(replay) ")?;
+            output.flush()?;
+        }
+        for line in input.lines() {
+            let line = line?;
+            let words = line.split_whitespace().collect_vec();
+            let result = match words[..] {
+                ["quit" | "q"] => break,
+                [] | ["next" | "n"] => self
+                    .step_forward()
+                    .map(|moved| (!moved).then(|| "Already at the last step".to_string())),
+                ["previous" | "p"] => {
+                    Ok((!self.step_back()).then(|| "Already at the start".to_string()))
+                }
+                ["goto" | "g", n] => match n.parse() {
+                    Ok(n) => self.jump_to(n).map(|()| None),
+                    Err(_) => Ok(Some(format!("Not a step: {n}"))),
+                },
+                _ => Ok(Some(format!("Unknown command: {line}\n{REPLAY_HELP}"))),
+            };
+            match result {
+                Ok(None) => writeln!(output, "{}", self.frame())?,
+                Ok(Some(message)) => writeln!(output, "{message}")?,
+                Err(e) => writeln!(output, "{e}")?,
+            }
+            if prompt {
+                write!(output, "(replay) ")?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the current and all following frames.
+    fn frames(&mut self) -> Result<Vec<String>, InstructionError> {
+        let mut frames = vec![self.frame()];
+        while self.step_forward()? {
+            frames.push(self.frame());
+        }
+        Ok(frames)
+    }
+
+    fn dump(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let frames = self.frames()?;
+        std::fs::write(path, frames.join("\n\n"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n\nmove 1 from 2 to 1\nmove 3 from 1 to 3\nmove 2 from 2 to 1\nmove 1 from 1 to 2\n";
+
+    #[test]
+    fn test_crate_mover_9000() {
+        assert_eq!(rearrange(EXAMPLE, &CrateMover9000).unwrap().tops(), "CMZ");
+    }
+
+    #[test]
+    fn test_crate_mover_9001() {
+        assert_eq!(rearrange(EXAMPLE, &CrateMover9001).unwrap().tops(), "MCD");
+    }
+
+    #[test]
+    fn test_limited_crane() {
+        let mut tower = vec!['A', 'B', 'C', 'D', 'E'];
+        assert_eq!(
+            LimitedCrane { capacity: 2 }.pick_up(&mut tower, 5),
+            vec!['D', 'E', 'B', 'C', 'A']
+        );
+        assert!(tower.is_empty());
+
+        assert_eq!(
+            rearrange(EXAMPLE, &LimitedCrane { capacity: 1 })
+                .unwrap()
+                .tops(),
+            "CMZ"
+        );
+        assert_eq!(
+            rearrange(EXAMPLE, &LimitedCrane { capacity: 3 })
+                .unwrap()
+                .tops(),
+            "MCD"
+        );
+    }
+
+    #[test]
+    fn test_bottom_crane() {
+        let mut tower = vec!['A', 'B', 'C'];
+        assert_eq!(BottomCrane.pick_up(&mut tower, 2), vec!['A', 'B']);
+        assert_eq!(tower, vec!['C']);
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        let src = "move 3 from 1 to 12";
+        let step = parse_instruction(src, src).unwrap();
+        assert_eq!(
+            step.instruction,
+            Instruction {
+                amount: 3,
+                from: 0,
+                to: 11
+            }
+        );
+        assert_eq!(step.to_span, (17, 2).into());
+    }
+
+    #[test]
+    fn test_parse_instruction_errors() {
+        let src = "move 3 form 1 to 2";
+        assert!(matches!(
+            parse_instruction(src, src),
+            Err(InstructionError::Malformed { .. })
+        ));
+
+        let src = "move 3 from 0 to 2";
+        match parse_instruction(src, src) {
+            Err(InstructionError::ZeroTower { span, .. }) => assert_eq!(span, (12, 1).into()),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_input_errors() {
+        assert!(matches!(
+            parse_input("[A]\n 1 \nmove 1 from 1 to 1\n"),
+            Err(InstructionError::MissingSeparator { .. })
+        ));
+
+        let src = "[A]\n 1 \n[B]\n\nmove 1 from 1 to 1\n";
+        match parse_input(src) {
+            Err(InstructionError::MalformedDrawing { reason, span, .. }) => {
+                assert!(reason.contains("Unexpected character"), "{reason}");
+                assert_eq!(span, (0, 11).into());
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_tower() {
+        let input = EXAMPLE.replace("move 2 from 2 to 1", "move 2 from 2 to 4");
+        match rearrange(&input, &CrateMover9000) {
+            Err(
+                e @ InstructionError::UnknownTower {
+                    tower: 4, count: 3, ..
+                },
+            ) => {
+                assert_eq!(
+                    e.to_string(),
+                    "tower 4 does not exist in `move 2 from 2 to 4`"
+                )
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_not_enough_crates() {
+        let input = EXAMPLE.replace("move 3 from 1 to 3", "move 4 from 1 to 3");
+        match rearrange(&input, &CrateMover9000) {
+            Err(
+                e @ InstructionError::NotEnoughCrates {
+                    amount: 4,
+                    tower: 1,
+                    available: 3,
+                    ..
+                },
+            ) => {
+                assert_eq!(
+                    e.to_string(),
+                    "cannot move 4 crates from tower 1 in `move 4 from 1 to 3`"
+                );
+                let towers = miette::Diagnostic::help(&e).unwrap().to_string();
+                assert!(towers.contains("[Z] [M] [P]"), "{towers}");
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let (towers, steps) = parse_input(EXAMPLE).unwrap();
+        let mut replay = Replay::new(towers, steps, CrateMover9000);
+        assert!(!replay.step_back());
+
+        replay.jump_to(4).unwrap();
+        assert_eq!(replay.towers.tops(), "CMZ");
+        assert!(!replay.step_forward().unwrap());
+
+        replay.jump_to(1).unwrap();
+        assert_eq!(
+            replay.towers.0,
+            vec![vec!['Z', 'N', 'D'], vec!['M', 'C'], vec!['P']]
+        );
+        assert!(replay.step_back());
+        assert_eq!(replay.towers.tops(), "NDP");
+
+        let frames = replay.frames().unwrap();
+        assert_eq!(frames.len(), 5);
+        assert!(frames[2].starts_with("Step 2/4: move 3 from 1 to 3\n"));
+    }
+
+    #[test]
+    fn test_replay_browse() {
+        let (towers, steps) = parse_input(EXAMPLE).unwrap();
+        let mut replay = Replay::new(towers, steps, CrateMover9001);
+        let mut output = vec![];
+        replay
+            .browse(
+                "n\n\ngoto 4\nn\np\ng x\nfoo\nprevious\nq\nn\n".as_bytes(),
+                &mut output,
+                false,
+            )
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let titles = output
+            .lines()
+            .filter(|line| !line.starts_with(['[', ' ', '-']))
+            .collect_vec();
+        assert_eq!(
+            titles,
+            vec![
+                "Step 0/4: start",
+                "Step 1/4: move 1 from 2 to 1",
+                "Step 2/4: move 3 from 1 to 3",
+                "Step 4/4: move 1 from 1 to 2",
+                "Already at the last step",
+                "Step 3/4: move 2 from 2 to 1",
+                "Not a step: x",
+                "Unknown command: foo",
+                "Commands:",
+                "Step 2/4: move 3 from 1 to 3",
+            ]
+        );
+        assert_eq!(replay.position(), 2);
+    }
+
+    #[test]
+    fn test_replay_dump() {
+        let (towers, steps) = parse_input(EXAMPLE).unwrap();
+        let mut replay = Replay::new(towers, steps, CrateMover9000);
+        let frames = replay.frames().unwrap();
+        replay.jump_to(0).unwrap();
+        let path = std::env::temp_dir().join(format!("day05-test-{}.txt", std::process::id()));
+        replay.dump(&path).unwrap();
+        let dumped = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dumped, frames.join("\n\n"));
+        assert_eq!(dumped.matches("Step ").count(), 5);
+    }
+
+    #[test]
+    fn test_drawing_round_trip() {
+        let (drawing, _) = EXAMPLE.split_once("\n\n").unwrap();
+        let parsed = parse_drawing(drawing).unwrap();
+        assert_eq!(parsed.labels, vec!["1", "2", "3"]);
+        assert_eq!(parsed.to_string(), drawing);
+        assert_eq!(parsed.towers.to_string(), drawing);
+    }
+
+    #[test]
+    fn test_drawing_ragged_lines() {
+        let drawing = "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3";
+        let parsed = parse_drawing(drawing).unwrap();
+        assert_eq!(
+            parsed.towers.0,
+            vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]
+        );
+    }
+
+    #[test]
+    fn test_drawing_many_towers() {
+        let towers = Towers(
+            (0..12)
+                .map(|i| vec![(b'A' + i) as char; i as usize % 3])
+                .collect(),
+        );
+        let drawing = towers.to_string();
+        assert!(drawing.ends_with(" 9  10  11  12 "), "{drawing}");
+
+        let parsed = parse_drawing(&drawing).unwrap();
+        assert_eq!(parsed.labels.len(), 12);
+        assert_eq!(parsed.towers, towers);
+    }
+
+    #[test]
+    fn test_drawing_wide_labels() {
+        let drawing = Drawing {
+            labels: vec!["left".to_string(), "mid".to_string(), "right".to_string()],
+            towers: Towers(vec![vec!['A'], vec![], vec!['B', 'C']]),
+        };
+        let written = drawing.to_string();
+        assert_eq!(
+            written,
+            "             [C] \n [A]         [B] \nleft   mid  right"
+        );
+        assert_eq!(parse_drawing(&written).unwrap(), drawing);
+    }
+
+    #[test]
+    fn test_drawing_floating_crate() {
+        let drawing = "[A]    \n    [B]\n 1   2 ";
+        assert!(parse_drawing(drawing).is_err());
+    }
+
+    #[test]
+    fn test_final_arrangement_feeds_back() {
+        let end = rearrange(EXAMPLE, &CrateMover9001).unwrap();
+        let input = format!("{end}\n\n");
+        assert_eq!(rearrange(&input, &CrateMover9001).unwrap(), end);
+    }
+
+    #[test]
+    fn test_solve() {
+        let (start, _) = parse_input(EXAMPLE).unwrap();
+        let goal = rearrange(EXAMPLE, &CrateMover9000).unwrap();
+        let instructions = solve(&start, &goal, &CrateMover9000, 4).unwrap();
+        assert!(instructions.len() <= 4);
+
+        let input = format!("{start}\n\n{}\n", instructions.iter().join("\n"));
+        assert_eq!(rearrange(&input, &CrateMover9000).unwrap(), goal);
+    }
+
+    #[test]
+    fn test_solve_shortest() {
+        let start = Towers(vec![vec!['A', 'B'], vec![]]);
+        let goal = Towers(vec![vec![], vec!['A', 'B']]);
+        let instructions = solve(&start, &goal, &CrateMover9001, 3).unwrap();
+        assert_eq!(instructions.iter().join("\n"), "move 2 from 1 to 2");
+
+        let goal = Towers(vec![vec!['B'], vec!['A']]);
+        let instructions = solve(&start, &goal, &BottomCrane, 3).unwrap();
+        assert_eq!(instructions.iter().join("\n"), "move 1 from 1 to 2");
+
+        let goal = Towers(vec![vec![], vec!['A', 'B']]);
+        // One crate at a time can never put A under B with only two towers
+        assert_eq!(
+            solve(&start, &goal, &CrateMover9000, 10),
+            Err(PlanError::Unreachable)
+        );
+    }
+
+    #[test]
+    fn test_solve_real_input() {
+        let (start, steps) = parse_input(include_str!("../../input/05.txt")).unwrap();
+        for moves in 1..=4 {
+            let mut goal = start.clone();
+            for step in &steps[..moves] {
+                goal.perform_move(&CrateMover9001, step).unwrap();
+            }
+            let instructions = solve(&start, &goal, &CrateMover9001, 10).unwrap();
+            assert!(instructions.len() <= moves);
+
+            let mut towers = start.clone();
+            for instruction in instructions {
+                towers.apply(&CrateMover9001, instruction);
+            }
+            assert_eq!(towers, goal);
+        }
+    }
+
+    #[test]
+    fn test_solve_gives_up() {
+        let (start, steps) = parse_input(include_str!("../../input/05.txt")).unwrap();
+        let mut goal = start.clone();
+        for step in &steps[..4] {
+            goal.perform_move(&CrateMover9001, step).unwrap();
+        }
+        assert_eq!(
+            solve_within(&start, &goal, &CrateMover9001, 10, 100),
+            Err(PlanError::TooManyArrangements(100))
+        );
+    }
+
+    #[test]
+    fn test_solve_unreachable() {
+        let start = Towers(vec![vec!['A', 'B'], vec![], vec![]]);
+        let goal = Towers(vec![vec!['B', 'A'], vec![], vec![]]);
+        assert_eq!(
+            solve(&start, &goal, &CrateMover9001, 1),
+            Err(PlanError::TooManyMoves(1))
+        );
+        assert_eq!(
+            solve(&start, &goal, &CrateMover9001, 2),
+            Err(PlanError::TooManyMoves(2))
+        );
+        assert!(solve(&start, &goal, &CrateMover9001, 3).is_ok());
+
+        let goal = Towers(vec![vec!['A', 'C'], vec![], vec![]]);
+        assert_eq!(
+            solve(&start, &goal, &CrateMover9001, 10),
+            Err(PlanError::DifferentCrates)
+        );
+        let goal = Towers(vec![vec!['A', 'B'], vec![]]);
+        assert_eq!(
+            solve(&start, &goal, &CrateMover9001, 10),
+            Err(PlanError::TowerCount { start: 3, goal: 2 })
+        );
+    }
+}
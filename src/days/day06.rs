@@ -1,4 +1,6 @@
 use std::collections::HashSet;
+use std::io::{self, BufReader, Read};
+use std::time::Instant;
 
 pub fn day06() {
     let input = include_str!("../../input/06.txt");
@@ -6,27 +8,316 @@ pub fn day06() {
     day06b(input);
 }
 
+/// Finds the markers with windows of `packet_len` and `message_len` bytes in a datastream piped
+/// into stdin, without keeping it in memory.
+pub fn day06_stream(packet_len: usize, message_len: usize) -> io::Result<()> {
+    let mut first_packet = None;
+    let mut first_message = None;
+    let mut counts = (0, 0);
+    for marker in Markers::from_reader(io::stdin().lock(), packet_len, message_len) {
+        let Marker { kind, position } = marker?;
+        match kind {
+            MarkerKind::Packet => {
+                first_packet.get_or_insert(position);
+                counts.0 += 1;
+            }
+            MarkerKind::Message => {
+                first_message.get_or_insert(position);
+                counts.1 += 1;
+            }
+        }
+    }
+    println!("Start of packet: {first_packet:?} ({} markers)", counts.0);
+    println!("Start of message: {first_message:?} ({} markers)", counts.1);
+    Ok(())
+}
+
+/// Compares the marker detectors on a generated stream of `size` bytes.
+pub fn day06_bench(size: usize) {
+    for len in [4, 14, 26, 100] {
+        let stream = generate_stream(size, len);
+
+        let start = Instant::now();
+        let fast = solve_for_len(&stream, len);
+        let fast_time = start.elapsed();
+
+        let start = Instant::now();
+        let naive = solve_for_len_hashset(&stream, len);
+        let naive_time = start.elapsed();
+
+        assert_eq!(fast, naive);
+        let fast = fast.unwrap();
+        println!("Window {len:>2}, {size} bytes: last seen {fast_time:?}, hashset {naive_time:?}");
+    }
+}
+
 fn day06a(input: &str) {
-    let first = solve_for_len(input, 4);
-    println!("Day06a: {first}");
+    match solve_for_len(input.as_bytes(), PACKET_LEN) {
+        Some(first) => println!("Day06a: {first}"),
+        None => println!("Day06a: no start-of-packet marker"),
+    }
 }
 
 fn day06b(input: &str) {
-    let first = solve_for_len(input, 14);
-    println!("Day06a: {first}");
+    match solve_for_len(input.as_bytes(), MESSAGE_LEN) {
+        Some(first) => println!("Day06b: {first}"),
+        None => println!("Day06b: no start-of-message marker"),
+    }
+}
+
+/// The length of a start-of-packet marker in the puzzle.
+pub const PACKET_LEN: usize = 4;
+/// The length of a start-of-message marker in the puzzle.
+pub const MESSAGE_LEN: usize = 14;
+
+fn solve_for_len(input: &[u8], len: usize) -> Option<usize> {
+    let mut detector = Detector::new(len);
+    input
+        .iter()
+        .position(|byte| detector.push(*byte))
+        .map(|index| index + 1)
 }
 
-fn solve_for_len(input: &str, len: usize) -> usize {
-    let bytes: Vec<_> = input.bytes().collect();
-    bytes
+fn solve_for_len_hashset(input: &[u8], len: usize) -> Option<usize> {
+    input
         .windows(len)
         .enumerate()
         .find(|(_index, slice)| all_unique(slice))
         .map(|(index, _)| index + len)
-        .unwrap()
 }
 
 fn all_unique(slice: &&[u8]) -> bool {
     let hs: HashSet<_> = slice.iter().collect();
     hs.len() == slice.len()
 }
+
+/// Checks for windows of `len` distinct bytes in O(1) per byte, by remembering where every byte
+/// was last seen and moving the start of the window behind any repeated byte.
+struct Detector {
+    len: usize,
+    last_seen: [Option<usize>; 256],
+    start: usize,
+    index: usize,
+}
+
+impl Detector {
+    fn new(len: usize) -> Self {
+        Detector {
+            len,
+            last_seen: [None; 256],
+            start: 0,
+            index: 0,
+        }
+    }
+
+    /// Adds the next byte and returns whether the last `len` bytes are all different.
+    fn push(&mut self, byte: u8) -> bool {
+        if let Some(last) = self.last_seen[byte as usize] {
+            self.start = self.start.max(last + 1);
+        }
+        self.last_seen[byte as usize] = Some(self.index);
+        self.index += 1;
+        self.index - self.start >= self.len
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkerKind {
+    Packet,
+    Message,
+}
+
+/// A marker ending after `position` bytes of the datastream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Marker {
+    kind: MarkerKind,
+    position: usize,
+}
+
+/// Yields every start-of-packet and start-of-message marker while consuming the datastream.
+struct Markers<I> {
+    bytes: I,
+    packet: Detector,
+    message: Detector,
+    pending: Option<Marker>,
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Markers<I> {
+    fn new(bytes: I, packet_len: usize, message_len: usize) -> Self {
+        Markers {
+            bytes,
+            packet: Detector::new(packet_len),
+            message: Detector::new(message_len),
+            pending: None,
+        }
+    }
+}
+
+impl<R: Read> Markers<io::Bytes<BufReader<R>>> {
+    fn from_reader(reader: R, packet_len: usize, message_len: usize) -> Self {
+        Markers::new(BufReader::new(reader).bytes(), packet_len, message_len)
+    }
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Iterator for Markers<I> {
+    type Item = io::Result<Marker>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(marker) = self.pending.take() {
+            return Some(Ok(marker));
+        }
+        for byte in self.bytes.by_ref() {
+            let byte = match byte {
+                Ok(byte) => byte,
+                Err(e) => return Some(Err(e)),
+            };
+            let position = self.packet.index + 1;
+            let packet = self.packet.push(byte);
+            let message = self.message.push(byte);
+            if message {
+                self.pending = Some(Marker {
+                    kind: MarkerKind::Message,
+                    position,
+                });
+            }
+            if packet {
+                return Some(Ok(Marker {
+                    kind: MarkerKind::Packet,
+                    position,
+                }));
+            }
+            if let Some(marker) = self.pending.take() {
+                return Some(Ok(marker));
+            }
+        }
+        None
+    }
+}
+
+/// Generates a stream of `size` pseudo random bytes whose first marker of length `len` is at the
+/// very end, by only using `len - 1` different bytes before it. The marker is preceded by a run of
+/// its own first byte, so no window overlapping the marker is a marker itself.
+fn generate_stream(size: usize, len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2022_0006;
+    let alphabet: Vec<u8> = (0..=255).collect();
+    let mut stream: Vec<u8> = (0..size.saturating_sub(2 * len - 1))
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            alphabet[(state >> 33) as usize % (len - 1).max(1)]
+        })
+        .collect();
+    stream.extend(std::iter::repeat_n(alphabet[0], len - 1));
+    stream.extend(&alphabet[..len]);
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_for_len() {
+        for (input, a, b) in [
+            ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+            ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+            ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+            ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsgwb", 10, 29),
+            ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+        ] {
+            assert_eq!(solve_for_len(input.as_bytes(), 4), Some(a));
+            assert_eq!(solve_for_len(input.as_bytes(), 14), Some(b));
+        }
+    }
+
+    #[test]
+    fn test_generated_stream() {
+        for len in [2, 4, 14, 26, 100] {
+            let stream = generate_stream(10_000, len);
+            assert_eq!(solve_for_len(&stream, len), Some(10_000));
+            assert_eq!(solve_for_len_hashset(&stream, len), Some(10_000));
+        }
+    }
+
+    #[test]
+    fn test_no_marker() {
+        assert_eq!(solve_for_len(b"abcabcabc", 4), None);
+        assert_eq!(solve_for_len(b"", 4), None);
+        assert_eq!(
+            Markers::from_reader(&b"abcabcabc"[..], PACKET_LEN, MESSAGE_LEN).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_markers() {
+        let markers: Vec<_> =
+            Markers::from_reader(&b"aabcdeeabcdefghijklmn"[..], PACKET_LEN, MESSAGE_LEN)
+                .map(Result::unwrap)
+                .collect();
+        let packets = markers
+            .iter()
+            .filter(|marker| marker.kind == MarkerKind::Packet)
+            .map(|marker| marker.position)
+            .collect::<Vec<_>>();
+        let messages = markers
+            .iter()
+            .filter(|marker| marker.kind == MarkerKind::Message)
+            .map(|marker| marker.position)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            packets,
+            vec![5, 6, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21]
+        );
+        assert_eq!(messages, vec![21]);
+        assert_eq!(
+            markers[markers.len() - 2..],
+            [
+                Marker {
+                    kind: MarkerKind::Packet,
+                    position: 21
+                },
+                Marker {
+                    kind: MarkerKind::Message,
+                    position: 21
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markers_match_first() {
+        let input = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsgwb";
+        let mut markers =
+            Markers::new(input.bytes().map(Ok), PACKET_LEN, MESSAGE_LEN).map(Result::unwrap);
+        let first_packet = markers
+            .by_ref()
+            .find(|marker| marker.kind == MarkerKind::Packet);
+        assert_eq!(first_packet.map(|marker| marker.position), Some(10));
+        let first_message = markers.find(|marker| marker.kind == MarkerKind::Message);
+        assert_eq!(first_message.map(|marker| marker.position), Some(29));
+    }
+
+    #[test]
+    fn test_markers_window_lengths() {
+        let markers: Vec<_> = Markers::from_reader(&b"abacbdcxyz"[..], 3, 5)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            markers
+                .iter()
+                .map(|marker| (marker.kind, marker.position))
+                .collect::<Vec<_>>(),
+            vec![
+                (MarkerKind::Packet, 4),
+                (MarkerKind::Packet, 5),
+                (MarkerKind::Packet, 6),
+                (MarkerKind::Packet, 7),
+                (MarkerKind::Packet, 8),
+                (MarkerKind::Packet, 9),
+                (MarkerKind::Message, 9),
+                (MarkerKind::Packet, 10),
+                (MarkerKind::Message, 10),
+            ]
+        );
+    }
+}
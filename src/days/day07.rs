@@ -1,32 +1,143 @@
-use std::iter::once;
+use std::fs::File;
+use std::io;
+use std::iter::{once, successors};
+use std::path::{Path, PathBuf};
 
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+use miette::{GraphicalReportHandler, SourceSpan};
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_till, take_till1};
-use nom::character::complete::{char, digit1, line_ending};
-use nom::combinator::{map_res, opt, recognize};
-use nom::multi::many0;
-use nom::sequence::{preceded, separated_pair, terminated};
-use nom::IResult;
+use nom::bytes::complete::{escaped_transform, is_not, tag, take_till, take_while_m_n};
+use nom::character::complete::{char, digit1, line_ending, multispace0};
+use nom::combinator::{all_consuming, eof, map, map_opt, map_res, opt, recognize, value};
+use nom::multi::separated_list0;
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated};
+use nom::{IResult, Offset};
 
 pub fn day07() {
     let input = include_str!("../../input/07.txt");
-    let (_, root) = parse_non_exiting_cd(input).unwrap();
-    day07a(&root);
-    day07b(&root);
+    let Some(fs) = interpret_or_report(input) else {
+        return;
+    };
+    day07a(&fs);
+    day07b(&fs);
+}
+
+/// Runs a `du`, `find`, `largest` or `tree` query given as command line arguments against the
+/// file system of the puzzle input.
+pub fn day07_query(args: &[String]) {
+    let input = include_str!("../../input/07.txt");
+    let Some(fs) = interpret_or_report(input) else {
+        return;
+    };
+    match parse_query(args) {
+        Ok(query) => {
+            for line in fs.query(&query) {
+                println!("{line}");
+            }
+        }
+        Err(e) => println!("{e}\n{QUERY_USAGE}"),
+    }
+}
+
+/// Exports the file system of the puzzle input as `json`, as a `transcript` or as a `dir` of sparse
+/// files below the given path, or below the temp directory if no path is given.
+pub fn day07_export(args: &[String]) {
+    let input = include_str!("../../input/07.txt");
+    let Some(fs) = interpret_or_report(input) else {
+        return;
+    };
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["json"] => println!("{}", fs.to_json()),
+        ["transcript"] => print!("{}", fs.to_transcript()),
+        ["dir", ..] => {
+            let dir = args
+                .get(1)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| std::env::temp_dir().join("day07"));
+            match fs.materialize(&dir) {
+                Ok(()) => println!("Created file system in {}", dir.display()),
+                Err(e) => println!("Cannot create file system in {}: {e}", dir.display()),
+            }
+        }
+        _ => println!("Usage: json | transcript | dir [<path>]"),
+    }
 }
 
-fn day07a(root: &Entry) {
-    let sum: usize = root.sizes().filter(|size| *size <= 100000usize).sum();
+/// Answers both parts for a file system that was exported as `json` to the file at `path`.
+pub fn day07_import(path: &Path) {
+    let tree = match std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| parse_json(&json))
+    {
+        Ok(tree) => tree,
+        Err(e) => {
+            println!("Cannot import {}: {e}", path.display());
+            return;
+        }
+    };
+    match FileSystem::from_json(&tree) {
+        Ok(fs) => {
+            day07a(&fs);
+            day07b(&fs);
+        }
+        Err(e) => println!("Cannot import {}: {e}", path.display()),
+    }
+}
+
+/// Plans which folders of the puzzle input to delete to get `required` bytes free on a disk of
+/// `disk` bytes, once freeing as few bytes and once deleting as few folders as possible.
+pub fn day07_plan(disk: usize, required: usize) {
+    let input = include_str!("../../input/07.txt");
+    let Some(fs) = interpret_or_report(input) else {
+        return;
+    };
+    let needed = fs.space_needed(disk, required);
+    println!("Need to free {needed} bytes");
+    for (title, plan) in [
+        ("Fewest bytes", fs.smallest_deletion(needed)),
+        ("Fewest folders", fs.fewest_deletions(needed)),
+    ] {
+        match plan {
+            Some(plan) => {
+                println!(
+                    "{title}: {} bytes in {} folders",
+                    plan.freed,
+                    plan.folders.len()
+                );
+                for folder in plan.folders {
+                    println!("  {}\t{}", fs.size(folder), fs.path(folder));
+                }
+            }
+            None => println!("{title}: not possible"),
+        }
+    }
+}
+
+fn interpret_or_report(input: &str) -> Option<FileSystem<'_>> {
+    match interpret(input) {
+        Ok(fs) => Some(fs),
+        Err(e) => {
+            let mut s = String::new();
+            GraphicalReportHandler::new()
+                .render_report(&mut s, &e)
+                .unwrap();
+            println!("{s}");
+            None
+        }
+    }
+}
+
+fn day07a(fs: &FileSystem) {
+    let sum: usize = fs.sizes().filter(|size| *size <= 100000usize).sum();
 
     println!("Day07a: {sum}")
 }
 
-fn day07b(root: &Entry) {
-    let total_space = 70000000;
-    let used_space = root.size();
-    let needed_space = 30000000 - (total_space - used_space);
+fn day07b(fs: &FileSystem) {
+    let needed_space = fs.space_needed(DISK_SIZE, REQUIRED_SPACE);
 
-    let min: usize = root
+    let min: usize = fs
         .sizes()
         .filter(|size| *size >= needed_space)
         .min()
@@ -35,6 +146,112 @@ fn day07b(root: &Entry) {
     println!("Day07b: {min}")
 }
 
+const DISK_SIZE: usize = 70000000;
+const REQUIRED_SPACE: usize = 30000000;
+
+#[derive(thiserror::Error, Debug, miette::Diagnostic)]
+enum TranscriptError {
+    #[error("unknown command `{command}` in line {line}")]
+    UnknownCommand {
+        command: String,
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("expected `cd <dir>` or `ls`")]
+        span: SourceSpan,
+    },
+
+    #[error("unexpected output `{output}` in line {line}")]
+    UnexpectedOutput {
+        output: String,
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("expected `dir <name>` or `<size> <name>` after `$ ls`")]
+        span: SourceSpan,
+    },
+
+    #[error("cannot cd into file `{name}` in line {line}")]
+    NotADirectory {
+        name: String,
+        line: usize,
+        #[source_code]
+        src: String,
+        #[label("this is a file")]
+        span: SourceSpan,
+    },
+}
+
+/// Replays a shell transcript, keeping track of the current directory, and returns the file
+/// system with everything that was listed. Directories can be entered before they are listed,
+/// and listing a directory again only adds the entries that were not known yet.
+fn interpret(input: &str) -> Result<FileSystem<'_>, TranscriptError> {
+    let mut fs = FileSystem::new();
+    let mut cwd = ROOT;
+    let mut listing = false;
+
+    for (index, line) in input.lines().enumerate() {
+        let span: SourceSpan = (input.offset(line), line.len()).into();
+        let line_number = index + 1;
+        if let Some(command) = line.strip_prefix("$ ") {
+            listing = false;
+            match command.trim_end().split_once(' ') {
+                None if command.trim_end() == "ls" => listing = true,
+                Some(("cd", "/")) => cwd = ROOT,
+                Some(("cd", "..")) => cwd = fs.parent(cwd).unwrap_or(ROOT),
+                Some(("cd", name)) => {
+                    cwd = match fs.child(cwd, name) {
+                        Some(child) if fs.is_folder(child) => child,
+                        Some(_) => {
+                            return Err(TranscriptError::NotADirectory {
+                                name: name.to_string(),
+                                line: line_number,
+                                src: input.to_string(),
+                                span,
+                            })
+                        }
+                        None => fs.add_folder(cwd, name),
+                    }
+                }
+                _ => {
+                    return Err(TranscriptError::UnknownCommand {
+                        command: command.to_string(),
+                        line: line_number,
+                        src: input.to_string(),
+                        span,
+                    })
+                }
+            }
+        } else if line.trim().is_empty() {
+            continue;
+        } else {
+            let entry = match parse_entry(line) {
+                Ok((_, entry)) if listing => entry,
+                _ => {
+                    return Err(TranscriptError::UnexpectedOutput {
+                        output: line.to_string(),
+                        line: line_number,
+                        src: input.to_string(),
+                        span,
+                    })
+                }
+            };
+            match entry {
+                Entry::File { name, .. } | Entry::Folder { name }
+                    if fs.child(cwd, name).is_some() => {}
+                Entry::File { name, size } => {
+                    fs.add_file(cwd, name, size);
+                }
+                Entry::Folder { name } => {
+                    fs.add_folder(cwd, name);
+                }
+            }
+        }
+    }
+
+    Ok(fs)
+}
+
 fn my_usize(input: &str) -> IResult<&str, usize> {
     map_res(recognize(digit1), str::parse)(input)
 }
@@ -43,134 +260,904 @@ fn is_line_ending(c: char) -> bool {
     c == '\n' || c == '\r'
 }
 
-fn parse_file(input: &str) -> IResult<&str, Entry> {
+fn parse_file(input: &str) -> IResult<&str, Entry<'_>> {
     let (input, (size, name)) = terminated(
         separated_pair(my_usize, char(' '), take_till(is_line_ending)),
-        line_ending,
+        alt((line_ending, eof)),
     )(input)?;
     let entry = Entry::File { size, name };
 
     Ok((input, entry))
 }
 
-fn parse_folder(input: &str) -> IResult<&str, Entry> {
+fn parse_folder(input: &str) -> IResult<&str, Entry<'_>> {
     let parse_line = preceded(tag("dir "), take_till(is_line_ending));
-    let mut parse_with_line_ending = terminated(parse_line, line_ending);
+    let mut parse_with_line_ending = terminated(parse_line, alt((line_ending, eof)));
     let (input, name) = parse_with_line_ending(input)?;
-    let entry = Entry::Folder {
-        name,
-        entries: vec![],
-    };
+    let entry = Entry::Folder { name };
     Ok((input, entry))
 }
 
-fn parse_entry(input: &str) -> IResult<&str, Entry> {
+fn parse_entry(input: &str) -> IResult<&str, Entry<'_>> {
     alt((parse_file, parse_folder))(input)
 }
 
-fn parse_ls(input: &str) -> IResult<&str, Vec<Entry>> {
-    let (input, _) = terminated(tag("$ ls"), line_ending)(input)?;
-    let (input, mut file_list) = many0(parse_entry)(input)?;
-    file_list.retain(|entry| match entry {
-        Entry::File { .. } => true,
-        Entry::Folder { .. } => false,
-    });
-    let result = many0(parse_exiting_cd)(input); // Many0 does something i dont understand, which leads to the input being incorrectly consumed here
-    let (input, mut folders) = result?;
-    let (input, folder) = opt(parse_non_exiting_cd)(input)?;
-    file_list.append(&mut folders);
-    if let Some(entry) = folder {
-        file_list.push(entry);
-    }
-    Ok((input, file_list))
-}
-
-fn parse_non_exiting_cd(input: &str) -> IResult<&str, Entry> {
-    let (input, _) = tag("$ cd ")(input)?;
-    let (input, folder_name) = take_till1(is_line_ending)(input)?;
-    let (input, _) = line_ending(input)?;
-    let (input, entries) = parse_ls(input)?;
-    let entry = Entry::Folder {
-        name: folder_name,
-        entries,
-    };
-    Ok((input, entry))
+/// A line of `ls` output.
+#[derive(Debug, Eq, PartialEq)]
+enum Entry<'a> {
+    File { name: &'a str, size: usize },
+    Folder { name: &'a str },
 }
 
-fn parse_exiting_cd(input: &str) -> IResult<&str, Entry> {
-    let (input, entry) = parse_non_exiting_cd(input)?;
-    let (input, _) = terminated(tag("$ cd .."), line_ending)(input)?;
-    Ok((input, entry))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct NodeId(usize);
+
+const ROOT: NodeId = NodeId(0);
+
+/// Most sums of folder sizes to keep track of when looking for the smallest deletion.
+const MAX_DELETION_SUMS: usize = 1 << 23;
+
+#[derive(Debug, PartialEq, Eq)]
+enum NodeKind {
+    File,
+    Folder { children: Vec<NodeId> },
 }
 
-#[derive(Debug, Eq, PartialEq)]
-enum Entry<'a> {
-    File {
-        name: &'a str,
-        size: usize,
-    },
-    Folder {
-        name: &'a str,
-        entries: Vec<Entry<'a>>,
-    },
+#[derive(Debug)]
+struct Node<'a> {
+    name: &'a str,
+    parent: Option<NodeId>,
+    /// Size of the file, or the cached total size of everything inside the folder
+    size: usize,
+    kind: NodeKind,
 }
 
-impl<'a> Entry<'a> {
-    fn sizes(&'a self) -> impl Iterator<Item = usize> + 'a {
-        self.entries()
-            .filter(|entry| match entry {
-                Entry::File { .. } => false,
-                Entry::Folder { .. } => true,
-            })
-            .map(|entry| entry.size())
+/// A file system tree stored in an arena. Nodes refer to each other by their [`NodeId`], and the
+/// root folder `/` is always [`ROOT`].
+#[derive(Debug)]
+struct FileSystem<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> FileSystem<'a> {
+    fn new() -> Self {
+        FileSystem {
+            nodes: vec![Node {
+                name: "/",
+                parent: None,
+                size: 0,
+                kind: NodeKind::Folder { children: vec![] },
+            }],
+        }
+    }
+
+    fn node(&self, id: NodeId) -> &Node<'a> {
+        &self.nodes[id.0]
+    }
+
+    fn name(&self, id: NodeId) -> &'a str {
+        self.node(id).name
+    }
+
+    fn size(&self, id: NodeId) -> usize {
+        self.node(id).size
+    }
+
+    fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
     }
 
-    pub(crate) fn size(&self) -> usize {
-        match self {
-            Entry::File { size, .. } => *size,
-            Entry::Folder { entries, .. } => entries.iter().map(|entry| entry.size()).sum(),
+    fn is_folder(&self, id: NodeId) -> bool {
+        matches!(self.node(id).kind, NodeKind::Folder { .. })
+    }
+
+    fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.node(id).kind {
+            NodeKind::File => &[],
+            NodeKind::Folder { children } => children,
         }
     }
 
-    pub fn entries(&'a self) -> Box<dyn Iterator<Item = &Entry> + 'a> {
-        match self {
-            Entry::File { .. } => Box::new(once(self)),
-            Entry::Folder { name: _, entries } => {
-                Box::new(once(self).chain(entries.iter().flat_map(|entry| entry.entries())))
-            }
+    fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id)
+            .iter()
+            .copied()
+            .find(|child| self.name(*child) == name)
+    }
+
+    fn add_folder(&mut self, parent: NodeId, name: &'a str) -> NodeId {
+        self.add(parent, name, 0, NodeKind::Folder { children: vec![] })
+    }
+
+    /// Adds a file and updates the cached sizes of all folders containing it.
+    fn add_file(&mut self, parent: NodeId, name: &'a str, size: usize) -> NodeId {
+        let id = self.add(parent, name, size, NodeKind::File);
+        for ancestor in self.ancestors(id).collect::<Vec<_>>() {
+            self.nodes[ancestor.0].size += size;
         }
+        id
+    }
+
+    fn add(&mut self, parent: NodeId, name: &'a str, size: usize, kind: NodeKind) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name,
+            parent: Some(parent),
+            size,
+            kind,
+        });
+        match &mut self.nodes[parent.0].kind {
+            NodeKind::Folder { children } => children.push(id),
+            NodeKind::File => panic!("{} is not a folder", self.name(parent)),
+        }
+        id
+    }
+
+    /// Finds a node by its absolute path, like `/a/e/i`.
+    fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.strip_prefix('/')?
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(ROOT, |id, name| self.child(id, name))
+    }
+
+    /// The absolute path of a node, like `/a/e/i`.
+    fn path(&self, id: NodeId) -> String {
+        if id == ROOT {
+            return "/".to_string();
+        }
+        let mut names: Vec<_> = once(id)
+            .chain(self.ancestors(id))
+            .filter(|id| *id != ROOT)
+            .map(|id| self.name(id))
+            .collect();
+        names.reverse();
+        names.iter().map(|name| format!("/{name}")).collect()
+    }
+
+    /// Number of folders between the root and the node, so the root has depth 0.
+    fn depth(&self, id: NodeId) -> usize {
+        self.ancestors(id).count()
+    }
+
+    /// The parent, grandparent and so on of a node, up to the root.
+    fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        successors(self.parent(id), |id| self.parent(*id))
+    }
+
+    /// Every node below `id` in depth first pre-order, not including `id` itself.
+    fn descendants(&self, id: NodeId) -> Descendants<'_, 'a> {
+        Descendants {
+            fs: self,
+            stack: self.children(id).iter().rev().copied().collect(),
+        }
+    }
+
+    /// The total sizes of all folders, starting with the root.
+    fn sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        once(ROOT)
+            .chain(self.descendants(ROOT))
+            .filter(|id| self.is_folder(*id))
+            .map(|id| self.size(id))
     }
 
     pub fn _pretty_print(&self) {
-        self._pretty_print_("", true);
+        let mut lines = vec![];
+        self.tree(ROOT, "", true, None, None, &mut lines);
+        println!("{}", lines.join("\n"));
     }
 
-    fn _pretty_print_(&self, indent: &str, last: bool) {
-        let size = self.size();
-        match self {
-            Entry::File { name, .. } => {
-                if last {
-                    println!("{indent}└── {name}\t{size}");
-                } else {
-                    println!("{indent}├── {name}\t{size}")
+    /// Draws the tree below `id` into `lines`, down to `max_depth` and with children in `sort` order.
+    fn tree(
+        &self,
+        id: NodeId,
+        indent: &str,
+        last: bool,
+        max_depth: Option<usize>,
+        sort: Option<SortBy>,
+        lines: &mut Vec<String>,
+    ) {
+        let name = self.name(id);
+        let size = self.size(id);
+        if last {
+            lines.push(format!("{indent}└── {name}\t{size}"));
+        } else {
+            lines.push(format!("{indent}├── {name}\t{size}"))
+        }
+
+        if max_depth.is_some_and(|max| self.depth(id) >= max) {
+            return;
+        }
+
+        let add_indent = if last { "    " } else { "│   " };
+        let indent = format!("{indent}{add_indent}");
+
+        let children = self.sorted(self.children(id).to_vec(), sort);
+        for (i, child) in children.iter().enumerate() {
+            self.tree(
+                *child,
+                &indent,
+                i == children.len() - 1,
+                max_depth,
+                sort,
+                lines,
+            )
+        }
+    }
+
+    fn sorted(&self, mut ids: Vec<NodeId>, sort: Option<SortBy>) -> Vec<NodeId> {
+        match sort {
+            Some(SortBy::Size) => ids.sort_by_key(|id| std::cmp::Reverse(self.size(*id))),
+            Some(SortBy::Name) => ids.sort_by_cached_key(|id| self.path(*id)),
+            None => {}
+        }
+        ids
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(ROOT, &mut json);
+        json
+    }
+
+    fn write_json(&self, id: NodeId, json: &mut String) {
+        let name = json_string(self.name(id));
+        let size = self.size(id);
+        if self.is_folder(id) {
+            json.push_str(&format!(
+                r#"{{"name":{name},"type":"dir","size":{size},"children":["#
+            ));
+            for (i, child) in self.children(id).iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
                 }
+                self.write_json(*child, json);
             }
-            Entry::Folder { name, entries } => {
-                if last {
-                    println!("{indent}└── {name}\t{size}");
-                } else {
-                    println!("{indent}├── {name}\t{size}")
+            json.push_str("]}");
+        } else {
+            json.push_str(&format!(r#"{{"name":{name},"type":"file","size":{size}}}"#));
+        }
+    }
+
+    /// Builds the tree written by [`FileSystem::to_json`]. The root has to be the folder `/`, and
+    /// the recorded size of every folder has to match the files inside it.
+    fn from_json(tree: &'a JsonNode) -> anyhow::Result<Self> {
+        if tree.name != "/" || tree.children.is_none() {
+            bail!("The root has to be the folder /, not {:?}", tree.name);
+        }
+        let mut fs = FileSystem::new();
+        fs.add_json(ROOT, tree)?;
+        Ok(fs)
+    }
+
+    fn add_json(&mut self, id: NodeId, tree: &'a JsonNode) -> anyhow::Result<()> {
+        for child in tree.children.iter().flatten() {
+            match &child.children {
+                Some(_) => {
+                    let folder = self.add_folder(id, &child.name);
+                    self.add_json(folder, child)?;
+                }
+                None => {
+                    self.add_file(id, &child.name, child.size);
+                }
+            }
+        }
+        if self.size(id) != tree.size {
+            bail!(
+                "{} has a size of {}, but its files add up to {}",
+                self.path(id),
+                tree.size,
+                self.size(id)
+            );
+        }
+        Ok(())
+    }
+
+    /// A transcript that lists every folder once, which [`interpret`] turns back into this tree.
+    fn to_transcript(&self) -> String {
+        let mut transcript = "$ cd /\n".to_string();
+        self.write_transcript(ROOT, &mut transcript);
+        transcript
+    }
+
+    fn write_transcript(&self, id: NodeId, transcript: &mut String) {
+        transcript.push_str("$ ls\n");
+        for child in self.children(id) {
+            let name = self.name(*child);
+            if self.is_folder(*child) {
+                transcript.push_str(&format!("dir {name}\n"));
+            } else {
+                transcript.push_str(&format!("{} {name}\n", self.size(*child)));
+            }
+        }
+        for child in self.children(id) {
+            if self.is_folder(*child) {
+                transcript.push_str(&format!("$ cd {}\n", self.name(*child)));
+                self.write_transcript(*child, transcript);
+                transcript.push_str("$ cd ..\n");
+            }
+        }
+    }
+
+    /// Recreates the tree below `dir`, with every file being a sparse file of the recorded size.
+    fn materialize(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for child in self.children(ROOT) {
+            self.materialize_node(*child, dir)?;
+        }
+        Ok(())
+    }
+
+    fn materialize_node(&self, id: NodeId, dir: &Path) -> io::Result<()> {
+        let name = self.name(id);
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{name:?} is not a valid file name"),
+            ));
+        }
+        let path = dir.join(name);
+        if self.is_folder(id) {
+            std::fs::create_dir_all(&path)?;
+            for child in self.children(id) {
+                self.materialize_node(*child, &path)?;
+            }
+        } else {
+            File::create(&path)?.set_len(self.size(id) as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Every node below `id` with children before their parents, like `du` lists them.
+    fn post_order(&self, id: NodeId, result: &mut Vec<NodeId>) {
+        for child in self.children(id) {
+            self.post_order(*child, result);
+        }
+        result.push(id);
+    }
+
+    fn query(&self, query: &Query) -> Vec<String> {
+        let within_depth = |id: &NodeId| query.depth.is_none_or(|max| self.depth(*id) <= max);
+        let matches_kind = |id: &NodeId| match query.kind {
+            Some(Kind::File) => !self.is_folder(*id),
+            Some(Kind::Folder) => self.is_folder(*id),
+            None => true,
+        };
+
+        match query.command {
+            QueryCommand::Du => {
+                let mut ids = vec![];
+                self.post_order(ROOT, &mut ids);
+                let ids = ids
+                    .into_iter()
+                    .filter(|id| self.is_folder(*id))
+                    .filter(within_depth)
+                    .collect();
+                self.sorted(ids, query.sort)
+                    .into_iter()
+                    .map(|id| format!("{}\t{}", self.size(id), self.path(id)))
+                    .collect()
+            }
+            QueryCommand::Find => {
+                let ids = once(ROOT)
+                    .chain(self.descendants(ROOT))
+                    .filter(within_depth)
+                    .filter(matches_kind)
+                    .filter(|id| {
+                        query
+                            .name
+                            .as_ref()
+                            .is_none_or(|pattern| glob_match(pattern, self.name(*id)))
+                    })
+                    .filter(|id| {
+                        query
+                            .size
+                            .is_none_or(|filter| filter.matches(self.size(*id)))
+                    })
+                    .collect();
+                self.sorted(ids, query.sort)
+                    .into_iter()
+                    .map(|id| self.path(id))
+                    .collect()
+            }
+            QueryCommand::Largest => {
+                let ids = once(ROOT)
+                    .chain(self.descendants(ROOT))
+                    .filter(within_depth)
+                    .filter(matches_kind)
+                    .collect();
+                self.sorted(ids, Some(SortBy::Size))
+                    .into_iter()
+                    .take(query.count)
+                    .map(|id| format!("{}\t{}", self.size(id), self.path(id)))
+                    .collect()
+            }
+            QueryCommand::Tree => {
+                let mut lines = vec![];
+                self.tree(ROOT, "", true, query.depth, query.sort, &mut lines);
+                lines
+            }
+        }
+    }
+}
+
+/// Folders to delete, none of them inside another, and the bytes this frees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DeletionPlan {
+    freed: usize,
+    folders: Vec<NodeId>,
+}
+
+impl FileSystem<'_> {
+    /// Bytes that have to be freed so that `required` bytes of a `disk` are unused.
+    fn space_needed(&self, disk: usize, required: usize) -> usize {
+        required.saturating_sub(disk.saturating_sub(self.size(ROOT)))
+    }
+
+    /// Finds the folders whose deletion frees the fewest bytes that are still at least `needed`.
+    /// The root itself cannot be deleted. Returns `None` if there is not enough to delete.
+    ///
+    /// See [`Self::smallest_deletion_within`], with a table of [`MAX_DELETION_SUMS`] entries.
+    fn smallest_deletion(&self, needed: usize) -> Option<DeletionPlan> {
+        self.smallest_deletion_within(needed, MAX_DELETION_SUMS)
+    }
+
+    /// This is a knapsack over the folders in pre-order, where deleting a folder skips everything
+    /// inside it. The bytes that can be freed from position `i` on are a superset of those from
+    /// `i + 1` on, so for every sum it is enough to remember the last position it can be reached
+    /// from, instead of a whole set of sums per position.
+    ///
+    /// That takes time proportional to the number of folders times the number of sums, and memory
+    /// for one entry per sum. To keep this bounded, at most `max_sums` sums are tracked: beyond
+    /// that sizes are counted in coarser units, rounded down, and the plan may free more than the
+    /// fewest bytes possible, by up to about one unit per folder.
+    fn smallest_deletion_within(&self, needed: usize, max_sums: usize) -> Option<DeletionPlan> {
+        let folders: Vec<NodeId> = self
+            .descendants(ROOT)
+            .filter(|id| self.is_folder(*id))
+            .collect();
+        // Position after the last folder inside of each folder
+        let ends: Vec<usize> = folders
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                i + 1
+                    + self
+                        .descendants(*id)
+                        .filter(|id| self.is_folder(*id))
+                        .count()
+            })
+            .collect();
+        let sizes: Vec<usize> = folders.iter().map(|id| self.size(*id)).collect();
+
+        // No plan needs to free more than the smallest single folder that is large enough, or
+        // else all the folders in the root
+        let fallback = match (0..folders.len())
+            .filter(|i| sizes[*i] >= needed)
+            .min_by_key(|i| sizes[*i])
+        {
+            Some(i) => DeletionPlan {
+                freed: sizes[i],
+                folders: vec![folders[i]],
+            },
+            None => {
+                let top: Vec<NodeId> = self
+                    .children(ROOT)
+                    .iter()
+                    .copied()
+                    .filter(|id| self.is_folder(*id))
+                    .collect();
+                DeletionPlan {
+                    freed: top.iter().map(|id| self.size(*id)).sum(),
+                    folders: top,
                 }
+            }
+        };
+        if fallback.freed < needed {
+            return None;
+        }
 
-                let add_indent = if last { "    " } else { "│   " };
-                let indent = format!("{indent}{add_indent}");
+        let unit = fallback.freed.div_ceil(max_sums).max(1);
+        let (target, limit) = (needed.div_ceil(unit), fallback.freed / unit);
+        let units: Vec<usize> = sizes.iter().map(|size| size / unit).collect();
 
-                for (i, entry) in entries.iter().enumerate() {
-                    entry._pretty_print_(&indent, i == entries.len() - 1)
+        // reach[sum] is one more than the last position `sum` can be freed from, or 0 if never
+        let n = folders.len();
+        let mut reach = vec![0u32; limit + 1];
+        reach[0] = n as u32 + 1;
+        let mut highest = 0;
+        for i in (0..n).rev() {
+            let (size, end) = (units[i], ends[i] as u32);
+            if size == 0 || size > limit {
+                continue;
+            }
+            for rest in 0..=highest.min(limit - size) {
+                if reach[rest] > end && reach[rest + size] == 0 {
+                    reach[rest + size] = i as u32 + 1;
                 }
             }
+            highest = (highest + size).min(limit);
+        }
+
+        // With coarser units, the fallback itself may round down to less than the target
+        let Some(sum) = (target..=limit).find(|sum| reach[*sum] > 0) else {
+            return Some(fallback);
+        };
+        let mut plan = DeletionPlan {
+            freed: 0,
+            folders: vec![],
+        };
+        let (mut remaining, mut i) = (sum, 0);
+        while remaining > 0 {
+            if reach[remaining] as usize > i + 1 {
+                i += 1;
+            } else {
+                plan.folders.push(folders[i]);
+                plan.freed += sizes[i];
+                remaining -= units[i];
+                i = ends[i];
+            }
+        }
+        // Rounding down can also make a combination look smaller than the fallback
+        if plan.freed > fallback.freed {
+            return Some(fallback);
         }
+        Some(plan)
     }
+
+    /// Finds as few folders as possible whose deletion frees at least `needed` bytes. A single
+    /// folder is the smallest one that is large enough, otherwise the plan freeing the most bytes
+    /// is chosen. Returns `None` if there is not enough to delete.
+    fn fewest_deletions(&self, needed: usize) -> Option<DeletionPlan> {
+        if needed == 0 {
+            return self.most_freed(ROOT).into_iter().next().flatten();
+        }
+        let smallest_folder = self
+            .descendants(ROOT)
+            .filter(|id| self.is_folder(*id) && self.size(*id) >= needed)
+            .min_by_key(|id| self.size(*id));
+        if let Some(id) = smallest_folder {
+            return Some(DeletionPlan {
+                freed: self.size(id),
+                folders: vec![id],
+            });
+        }
+        self.most_freed(ROOT)
+            .into_iter()
+            .flatten()
+            .find(|plan| plan.freed >= needed)
+    }
+
+    /// For every number of folders `k`, the plan deleting `k` folders below `id` that frees the
+    /// most bytes, or `None` if there are fewer than `k` folders to choose from.
+    fn most_freed(&self, id: NodeId) -> Vec<Option<DeletionPlan>> {
+        let empty = DeletionPlan {
+            freed: 0,
+            folders: vec![],
+        };
+        let mut best = vec![Some(empty)];
+        for child in self.children(id) {
+            if !self.is_folder(*child) {
+                continue;
+            }
+            let child_best = self.most_freed(*child);
+            let mut combined: Vec<Option<DeletionPlan>> =
+                vec![None; best.len() + child_best.len() - 1];
+            for (i, a) in best.iter().enumerate() {
+                for (j, b) in child_best.iter().enumerate() {
+                    let (Some(a), Some(b)) = (a, b) else {
+                        continue;
+                    };
+                    if combined[i + j]
+                        .as_ref()
+                        .is_none_or(|plan| plan.freed < a.freed + b.freed)
+                    {
+                        combined[i + j] = Some(DeletionPlan {
+                            freed: a.freed + b.freed,
+                            folders: [a.folders.as_slice(), b.folders.as_slice()].concat(),
+                        });
+                    }
+                }
+            }
+            best = combined;
+        }
+        if id != ROOT {
+            // Deleting the folder itself frees at least as much as any single folder inside it
+            if best.len() < 2 {
+                best.push(None);
+            }
+            best[1] = Some(DeletionPlan {
+                freed: self.size(id),
+                folders: vec![id],
+            });
+        }
+        best
+    }
+}
+
+struct Descendants<'fs, 'a> {
+    fs: &'fs FileSystem<'a>,
+    stack: Vec<NodeId>,
+}
+
+impl Iterator for Descendants<'_, '_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        self.stack
+            .extend(self.fs.children(id).iter().rev().copied());
+        Some(id)
+    }
+}
+
+const QUERY_USAGE: &str = "Usage:
+  du      [-d <depth>] [--sort size|name]
+  find    [-name <glob>] [-size [+|-]<bytes>] [-type f|d] [-d <depth>] [--sort size|name]
+  largest [-n <count>] [-type f|d] [-d <depth>]
+  tree    [-d <depth>] [--sort size|name]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryCommand {
+    Du,
+    Find,
+    Largest,
+    Tree,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    /// Largest first
+    Size,
+    /// Alphabetical by path
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    File,
+    Folder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SizeFilter {
+    Less(usize),
+    Greater(usize),
+    Exactly(usize),
+}
+
+impl SizeFilter {
+    fn matches(&self, size: usize) -> bool {
+        match *self {
+            SizeFilter::Less(limit) => size < limit,
+            SizeFilter::Greater(limit) => size > limit,
+            SizeFilter::Exactly(limit) => size == limit,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Query {
+    command: QueryCommand,
+    depth: Option<usize>,
+    sort: Option<SortBy>,
+    name: Option<String>,
+    size: Option<SizeFilter>,
+    kind: Option<Kind>,
+    count: usize,
+}
+
+fn parse_query(args: &[String]) -> anyhow::Result<Query> {
+    let (command, options) = args.split_first().ok_or(anyhow!("Missing query command"))?;
+    let command = match command.as_str() {
+        "du" => QueryCommand::Du,
+        "find" => QueryCommand::Find,
+        "largest" => QueryCommand::Largest,
+        "tree" => QueryCommand::Tree,
+        _ => bail!("Unknown query command {command}"),
+    };
+    let mut query = Query {
+        command,
+        depth: None,
+        sort: None,
+        name: None,
+        size: None,
+        kind: None,
+        count: 10,
+    };
+
+    if !options.len().is_multiple_of(2) {
+        bail!("Missing value for option {}", options[options.len() - 1]);
+    }
+
+    for (option, value) in options.iter().tuples() {
+        match (command, option.as_str()) {
+            (_, "-d") => query.depth = Some(value.parse()?),
+            (QueryCommand::Du | QueryCommand::Find | QueryCommand::Tree, "--sort") => {
+                query.sort = Some(match value.as_str() {
+                    "size" => SortBy::Size,
+                    "name" => SortBy::Name,
+                    _ => bail!("Cannot sort by {value}"),
+                })
+            }
+            (QueryCommand::Find, "-name") => query.name = Some(value.to_string()),
+            (QueryCommand::Find, "-size") => {
+                query.size = Some(if let Some(limit) = value.strip_prefix('+') {
+                    SizeFilter::Greater(limit.parse()?)
+                } else if let Some(limit) = value.strip_prefix('-') {
+                    SizeFilter::Less(limit.parse()?)
+                } else {
+                    SizeFilter::Exactly(value.parse()?)
+                })
+            }
+            (QueryCommand::Find | QueryCommand::Largest, "-type") => {
+                query.kind = Some(match value.as_str() {
+                    "f" => Kind::File,
+                    "d" => Kind::Folder,
+                    _ => bail!("Unknown type {value}"),
+                })
+            }
+            (QueryCommand::Largest, "-n") => query.count = value.parse()?,
+            _ => bail!("Unknown option {option} for {command:?}"),
+        }
+    }
+
+    Ok(query)
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// A file or folder as written by [`FileSystem::to_json`].
+#[derive(Debug, PartialEq, Eq)]
+struct JsonNode {
+    name: String,
+    size: usize,
+    /// The entries of a folder, or `None` for a file
+    children: Option<Vec<JsonNode>>,
+}
+
+/// One of the fields of a [`JsonNode`].
+enum JsonField {
+    Name(String),
+    Type(String),
+    Size(usize),
+    Children(Vec<JsonNode>),
+}
+
+fn parse_json(input: &str) -> anyhow::Result<JsonNode> {
+    match all_consuming(delimited(multispace0, json_node, multispace0))(input) {
+        Ok((_, node)) => Ok(node),
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+            let rest: String = e.input.chars().take(20).collect();
+            bail!("Invalid JSON at {rest:?}")
+        }
+        Err(nom::Err::Incomplete(_)) => bail!("Incomplete JSON"),
+    }
+}
+
+fn json_node(input: &str) -> IResult<&str, JsonNode> {
+    map_res(
+        delimited(
+            pair(char('{'), multispace0),
+            separated_list0(json_separator(','), json_field),
+            pair(multispace0, char('}')),
+        ),
+        |fields| {
+            let (mut name, mut kind, mut size, mut children) = (None, None, None, None);
+            for field in fields {
+                match field {
+                    JsonField::Name(value) => name = Some(value),
+                    JsonField::Type(value) => kind = Some(value),
+                    JsonField::Size(value) => size = Some(value),
+                    JsonField::Children(value) => children = Some(value),
+                }
+            }
+            let (Some(name), Some(size)) = (name, size) else {
+                return Err(anyhow!("Entry without a name or size"));
+            };
+            let children = match (kind.as_deref(), children) {
+                (Some("dir"), Some(children)) => Some(children),
+                (Some("file"), None) => None,
+                _ => return Err(anyhow!("{name} is neither a file nor a dir with children")),
+            };
+            Ok(JsonNode {
+                name,
+                size,
+                children,
+            })
+        },
+    )(input)
+}
+
+fn json_field(input: &str) -> IResult<&str, JsonField> {
+    alt((
+        map(preceded(json_key("name"), json_text), JsonField::Name),
+        map(preceded(json_key("type"), json_text), JsonField::Type),
+        map(preceded(json_key("size"), my_usize), JsonField::Size),
+        map(
+            preceded(
+                json_key("children"),
+                delimited(
+                    pair(char('['), multispace0),
+                    separated_list0(json_separator(','), json_node),
+                    pair(multispace0, char(']')),
+                ),
+            ),
+            JsonField::Children,
+        ),
+    ))(input)
+}
+
+fn json_key<'s>(key: &'static str) -> impl FnMut(&'s str) -> IResult<&'s str, char> {
+    preceded(
+        delimited(char('"'), tag(key), char('"')),
+        json_separator(':'),
+    )
+}
+
+fn json_separator<'s>(separator: char) -> impl FnMut(&'s str) -> IResult<&'s str, char> {
+    delimited(multispace0, char(separator), multispace0)
+}
+
+/// A string with the escapes written by [`json_string`], as well as the other single character
+/// escapes of JSON.
+fn json_text(input: &str) -> IResult<&str, String> {
+    let escape = alt((
+        value('"', char('"')),
+        value('\\', char('\\')),
+        value('/', char('/')),
+        value('\n', char('n')),
+        value('\r', char('r')),
+        value('\t', char('t')),
+        map_opt(
+            preceded(
+                char('u'),
+                take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+            ),
+            |hex| char::from_u32(u32::from_str_radix(hex, 16).ok()?),
+        ),
+    ));
+    delimited(
+        char('"'),
+        map(
+            opt(escaped_transform(is_not("\"\\"), '\\', escape)),
+            Option::unwrap_or_default,
+        ),
+        char('"'),
+    )(input)
+}
+
+/// Matches `name` against a shell glob, where `*` matches any sequence and `?` any single char.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matches[j] is whether the pattern so far matches the first j chars of the name
+    let mut matches = vec![false; name.len() + 1];
+    matches[0] = true;
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matches[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                c => j > 0 && matches[j - 1] && name[j - 1] == c,
+            };
+        }
+        matches = next;
+    }
+    matches[name.len()]
 }
 
 #[cfg(test)]
@@ -195,13 +1182,7 @@ mod test {
     fn test_parse_folder() {
         assert_eq!(
             parse_folder("dir a\n"),
-            Ok((
-                "",
-                Entry::Folder {
-                    name: "a",
-                    entries: vec![]
-                }
-            ))
+            Ok(("", Entry::Folder { name: "a" }))
         );
     }
 
@@ -220,123 +1201,337 @@ mod test {
 
         assert_eq!(
             parse_folder("dir f\n"),
-            Ok((
-                "",
-                Entry::Folder {
-                    name: "f",
-                    entries: vec![]
-                }
-            ))
+            Ok(("", Entry::Folder { name: "f" }))
         );
     }
 
+    const EXAMPLE: &str = "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls\ndir e\n29116 f\n2557 g\n62596 h.lst\n$ cd e\n$ ls\n584 i\n$ cd ..\n$ cd ..\n$ cd d\n$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k\n";
+
     #[test]
-    fn test_parse_ls() {
+    fn test_interpret() {
+        let fs = interpret(EXAMPLE).unwrap();
+        assert_eq!(fs.size(ROOT), 48381165);
         assert_eq!(
-            parse_ls("$ ls\n4060174 j\n8033020 d.log\n5626152 d.ext\n7214296 k\n"),
-            Ok((
-                "",
-                vec![
-                    Entry::File {
-                        name: "j",
-                        size: 4060174
-                    },
-                    Entry::File {
-                        name: "d.log",
-                        size: 8033020
-                    },
-                    Entry::File {
-                        name: "d.ext",
-                        size: 5626152
-                    },
-                    Entry::File {
-                        name: "k",
-                        size: 7214296
-                    },
-                ]
-            ))
+            fs.sizes().filter(|size| *size <= 100000).sum::<usize>(),
+            95437
+        );
+        assert_eq!(
+            fs.sizes().collect::<Vec<_>>(),
+            vec![48381165, 94853, 584, 24933642]
         );
     }
 
     #[test]
-    fn test_parse_non_exiting_cd() {
-        let input = "$ cd d\n$ ls\n4060174 b\n8033020 d.log\n5626152 d.ext\n7214296 k\n";
+    fn test_interpret_any_order() {
+        let input = "$ cd a\n$ cd b\n$ ls\n5 x\n$ cd /\n$ ls\n1 y\ndir a\n$ cd a\n$ ls\ndir b\n2 z\n$ ls\n2 z\n";
+        let fs = interpret(input).unwrap();
+        let paths: Vec<_> = fs
+            .descendants(ROOT)
+            .map(|id| (fs.path(id), fs.size(id)))
+            .collect();
         assert_eq!(
-            parse_non_exiting_cd(input),
-            Ok((
-                "",
-                Entry::Folder {
-                    name: "d",
-                    entries: vec![
-                        Entry::File {
-                            name: "b",
-                            size: 4060174
-                        },
-                        Entry::File {
-                            name: "d.log",
-                            size: 8033020
-                        },
-                        Entry::File {
-                            name: "d.ext",
-                            size: 5626152
-                        },
-                        Entry::File {
-                            name: "k",
-                            size: 7214296
-                        },
-                    ],
-                }
-            ))
+            paths,
+            vec![
+                ("/a".to_string(), 7),
+                ("/a/b".to_string(), 5),
+                ("/a/b/x".to_string(), 5),
+                ("/a/z".to_string(), 2),
+                ("/y".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let fs = interpret(EXAMPLE).unwrap();
+        assert_eq!(fs.lookup("/"), Some(ROOT));
+        let i = fs.lookup("/a/e/i").unwrap();
+        assert_eq!(fs.name(i), "i");
+        assert_eq!(fs.size(i), 584);
+        assert_eq!(fs.path(i), "/a/e/i");
+        assert_eq!(fs.lookup("/a/e/"), fs.parent(i));
+        assert_eq!(fs.lookup("/a/x"), None);
+        assert_eq!(fs.lookup("a/e"), None);
+    }
+
+    #[test]
+    fn test_ancestors_and_descendants() {
+        let fs = interpret(EXAMPLE).unwrap();
+        let i = fs.lookup("/a/e/i").unwrap();
+        let ancestors: Vec<_> = fs.ancestors(i).map(|id| fs.path(id)).collect();
+        assert_eq!(ancestors, vec!["/a/e", "/a", "/"]);
+
+        let a = fs.lookup("/a").unwrap();
+        let descendants: Vec<_> = fs.descendants(a).map(|id| fs.name(id)).collect();
+        assert_eq!(descendants, vec!["e", "i", "f", "g", "h.lst"]);
+        assert_eq!(fs.size(a), 94853);
+    }
+
+    #[test]
+    fn test_interpret_errors() {
+        match interpret("$ cd /\n$ ls\n1 a\n$ rm a\n") {
+            Err(TranscriptError::UnknownCommand { line, span, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(span, (16, 6).into());
+            }
+            other => panic!("{other:?}"),
+        }
+
+        match interpret("$ cd /\n1 a\n") {
+            Err(TranscriptError::UnexpectedOutput { line, .. }) => assert_eq!(line, 2),
+            other => panic!("{other:?}"),
+        }
+
+        match interpret("$ ls\n1 a\n$ cd a\n") {
+            Err(TranscriptError::NotADirectory { line, .. }) => assert_eq!(line, 3),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    fn query(fs: &FileSystem, args: &str) -> Vec<String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        fs.query(&parse_query(&args).unwrap())
+    }
+
+    #[test]
+    fn test_query_du() {
+        let fs = interpret(EXAMPLE).unwrap();
+        assert_eq!(
+            query(&fs, "du"),
+            vec!["584\t/a/e", "94853\t/a", "24933642\t/d", "48381165\t/"]
+        );
+        assert_eq!(
+            query(&fs, "du -d 1 --sort size"),
+            vec!["48381165\t/", "24933642\t/d", "94853\t/a"]
         );
+    }
 
-        let input = "$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n123 b.txt\n";
+    #[test]
+    fn test_query_find() {
+        let fs = interpret(EXAMPLE).unwrap();
         assert_eq!(
-            parse_non_exiting_cd(input),
-            Ok((
-                "",
-                Entry::Folder {
-                    name: "/",
-                    entries: vec![Entry::Folder {
-                        name: "a",
-                        entries: vec![Entry::File {
-                            name: "b.txt",
-                            size: 123
-                        }],
-                    }],
-                }
-            ))
+            query(&fs, "find -name *.*"),
+            vec!["/a/h.lst", "/b.txt", "/c.dat", "/d/d.log", "/d/d.ext"]
+        );
+        assert_eq!(
+            query(&fs, "find -name d.??? --sort name"),
+            vec!["/d/d.ext", "/d/d.log"]
+        );
+        assert_eq!(query(&fs, "find -type d -size -100000"), vec!["/a", "/a/e"]);
+        assert_eq!(
+            query(&fs, "find -size +10000000 -d 1"),
+            vec!["/", "/b.txt", "/d"]
         );
     }
 
     #[test]
-    fn test_parse_exiting_cd() {
-        let input = "$ cd d\n$ ls\n4060174 a\n8033020 d.log\n5626152 d.ext\n7214296 k\n$ cd ..\n";
+    fn test_query_largest() {
+        let fs = interpret(EXAMPLE).unwrap();
         assert_eq!(
-            parse_exiting_cd(input),
-            Ok((
-                "",
-                Entry::Folder {
-                    name: "d",
-                    entries: vec![
-                        Entry::File {
-                            name: "a",
-                            size: 4060174
-                        },
-                        Entry::File {
-                            name: "d.log",
-                            size: 8033020
-                        },
-                        Entry::File {
-                            name: "d.ext",
-                            size: 5626152
-                        },
-                        Entry::File {
-                            name: "k",
-                            size: 7214296
-                        },
-                    ],
-                }
-            ))
+            query(&fs, "largest -n 2 -type f"),
+            vec!["14848514\t/b.txt", "8504156\t/c.dat"]
+        );
+    }
+
+    #[test]
+    fn test_query_tree() {
+        let fs = interpret(EXAMPLE).unwrap();
+        assert_eq!(
+            query(&fs, "tree -d 1 --sort size").join("\n"),
+            "└── /\t48381165\n    ├── d\t24933642\n    ├── b.txt\t14848514\n    ├── c.dat\t8504156\n    └── a\t94853"
+        );
+    }
+
+    #[test]
+    fn test_parse_query_errors() {
+        let parse = |args: &str| {
+            parse_query(
+                &args
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert!(parse("").is_err());
+        assert!(parse("ls").is_err());
+        assert!(parse("du -name a").is_err());
+        assert!(parse("find -size").is_err());
+        assert!(parse("find -size é5").is_err());
+        assert!(parse("find -size +é5").is_err());
+        assert!(parse("tree --sort age").is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.txt", "b.txt"));
+        assert!(glob_match("d.?o?", "d.log"));
+        assert!(glob_match("a*b*c", "aXXbYbc"));
+        assert!(!glob_match("*.txt", "b.dat"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let fs = interpret("$ cd /\n$ ls\ndir a\n12 b\\\"c\n$ cd a\n$ ls\n3 d\n").unwrap();
+        assert_eq!(
+            fs.to_json(),
+            r#"{"name":"/","type":"dir","size":15,"children":[{"name":"a","type":"dir","size":3,"children":[{"name":"d","type":"file","size":3}]},{"name":"b\\\"c","type":"file","size":12}]}"#
         );
     }
+
+    #[test]
+    fn test_json_round_trip() {
+        let fs = interpret(EXAMPLE).unwrap();
+        let tree = parse_json(&fs.to_json()).unwrap();
+        let round_trip = FileSystem::from_json(&tree).unwrap();
+        assert_eq!(round_trip.to_json(), fs.to_json());
+        assert_eq!(round_trip.to_transcript(), fs.to_transcript());
+
+        let fs = interpret("$ cd /\n$ ls\ndir a\n12 b\\\"c\n$ cd a\n$ ls\n3 d\u{1}\n").unwrap();
+        let tree = parse_json(&fs.to_json()).unwrap();
+        assert_eq!(tree.children.as_ref().unwrap()[1].name, "b\\\"c");
+        let round_trip = FileSystem::from_json(&tree).unwrap();
+        assert_eq!(round_trip.to_json(), fs.to_json());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let json = r#" { "type" : "dir", "name": "/", "size": 5,
+            "children": [ {"name": "a\u00e9", "size": 5, "type": "file"} ] } "#;
+        let tree = parse_json(json).unwrap();
+        let fs = FileSystem::from_json(&tree).unwrap();
+        assert_eq!(fs.path(fs.children(ROOT)[0]), "/aé");
+
+        let tree = parse_json(r#"{"name":"/","type":"dir","size":4,"children":[]}"#).unwrap();
+        let e = FileSystem::from_json(&tree).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "/ has a size of 4, but its files add up to 0"
+        );
+
+        let tree = parse_json(r#"{"name":"a","type":"dir","size":0,"children":[]}"#).unwrap();
+        assert!(FileSystem::from_json(&tree).is_err());
+
+        assert!(parse_json(r#"{"name":"/","type":"file","size":0,"children":[]}"#).is_err());
+        assert!(parse_json(r#"{"name":"/","type":"dir","size":0,"children":[]"#).is_err());
+        assert!(parse_json(r#"{"name":"/","size":0}"#).is_err());
+    }
+
+    #[test]
+    fn test_transcript_round_trip() {
+        let fs = interpret(EXAMPLE).unwrap();
+        let transcript = fs.to_transcript();
+        assert_eq!(transcript, format!("{EXAMPLE}$ cd ..\n"));
+
+        let round_trip = interpret(&transcript).unwrap();
+        assert_eq!(round_trip.to_json(), fs.to_json());
+        assert_eq!(round_trip.to_transcript(), transcript);
+    }
+
+    #[test]
+    fn test_materialize() {
+        let fs = interpret(EXAMPLE).unwrap();
+        let dir = std::env::temp_dir().join(format!("day07-test-{}", std::process::id()));
+        fs.materialize(&dir).unwrap();
+
+        let i = std::fs::metadata(dir.join("a/e/i")).unwrap();
+        assert!(i.is_file());
+        assert_eq!(i.len(), 584);
+        assert!(std::fs::metadata(dir.join("d")).unwrap().is_dir());
+        let total: u64 = fs
+            .descendants(ROOT)
+            .filter(|id| !fs.is_folder(*id))
+            .map(|id| {
+                let path = dir.join(&fs.path(id)[1..]);
+                std::fs::metadata(path).unwrap().len()
+            })
+            .sum();
+        assert_eq!(total, fs.size(ROOT) as u64);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_space_needed() {
+        let fs = interpret(EXAMPLE).unwrap();
+        assert_eq!(fs.space_needed(DISK_SIZE, REQUIRED_SPACE), 8381165);
+        assert_eq!(fs.space_needed(100000000, REQUIRED_SPACE), 0);
+        assert_eq!(fs.space_needed(50000000, 10000000), 8381165);
+    }
+
+    #[test]
+    fn test_smallest_deletion() {
+        let fs = interpret(EXAMPLE).unwrap();
+        let plan = fs.smallest_deletion(8381165).unwrap();
+        assert_eq!(plan.freed, 24933642);
+        assert_eq!(plan.folders, vec![fs.lookup("/d").unwrap()]);
+
+        let plan = fs.smallest_deletion(95000).unwrap();
+        assert_eq!(plan.freed, 24933642);
+
+        let plan = fs.smallest_deletion(90000).unwrap();
+        assert_eq!(plan.freed, 94853);
+        assert_eq!(plan.folders, vec![fs.lookup("/a").unwrap()]);
+
+        let plan = fs.smallest_deletion(0).unwrap();
+        assert_eq!(plan.freed, 0);
+        assert!(plan.folders.is_empty());
+
+        assert_eq!(fs.smallest_deletion(25100000), None);
+    }
+
+    #[test]
+    fn test_smallest_deletion_combines_folders() {
+        let input = "$ cd /\n$ ls\ndir a\ndir b\ndir c\n$ cd a\n$ ls\n60 x\ndir e\n$ cd e\n$ ls\n40 y\n$ cd /\n$ cd b\n$ ls\n50 z\n$ cd /\n$ cd c\n$ ls\n55 w\n";
+        let fs = interpret(input).unwrap();
+
+        let plan = fs.smallest_deletion(90).unwrap();
+        assert_eq!(plan.freed, 90);
+        let paths: Vec<_> = plan.folders.iter().map(|id| fs.path(*id)).collect();
+        assert_eq!(paths, vec!["/a/e", "/b"]);
+
+        let plan = fs.fewest_deletions(90).unwrap();
+        assert_eq!(plan.freed, 100);
+        assert_eq!(plan.folders, vec![fs.lookup("/a").unwrap()]);
+
+        let plan = fs.fewest_deletions(52).unwrap();
+        assert_eq!(plan.freed, 55);
+        assert_eq!(plan.folders, vec![fs.lookup("/c").unwrap()]);
+
+        let plan = fs.fewest_deletions(120).unwrap();
+        assert_eq!(plan.freed, 155);
+        assert_eq!(plan.folders.len(), 2);
+
+        assert_eq!(fs.fewest_deletions(300), None);
+    }
+
+    #[test]
+    fn test_smallest_deletion_within() {
+        let fs = interpret(EXAMPLE).unwrap();
+        for max_sums in [1, 2, 1000, 100000000] {
+            let plan = fs.smallest_deletion_within(8381165, max_sums).unwrap();
+            assert_eq!(plan.freed, 24933642);
+        }
+        assert_eq!(fs.smallest_deletion_within(25100000, 10), None);
+
+        let input = "$ cd /\n$ ls\ndir a\ndir b\ndir c\n$ cd a\n$ ls\n60 x\ndir e\n$ cd e\n$ ls\n40 y\n$ cd /\n$ cd b\n$ ls\n50 z\n$ cd /\n$ cd c\n$ ls\n55 w\n";
+        let fs = interpret(input).unwrap();
+        assert_eq!(fs.smallest_deletion_within(90, 100).unwrap().freed, 90);
+        // In units of 10 bytes, /b and /c both count as 5, and the later one is picked
+        let plan = fs.smallest_deletion_within(90, 10).unwrap();
+        assert_eq!(plan.freed, 95);
+        let paths: Vec<_> = plan.folders.iter().map(|id| fs.path(*id)).collect();
+        assert_eq!(paths, vec!["/a/e", "/c"]);
+        // Too coarse to combine anything, so the smallest large enough folder is deleted
+        let plan = fs.smallest_deletion_within(90, 1).unwrap();
+        assert_eq!(plan.folders, vec![fs.lookup("/a").unwrap()]);
+
+        // In units of 10 bytes, /b and /c add up to as much as /a, but free more bytes
+        let input = "$ cd /\n$ ls\ndir a\ndir b\ndir c\n$ cd a\n$ ls\n100 x\n$ cd /\n$ cd b\n$ ls\n59 y\n$ cd /\n$ cd c\n$ ls\n59 z\n";
+        let fs = interpret(input).unwrap();
+        let plan = fs.smallest_deletion_within(90, 10).unwrap();
+        assert_eq!(plan.freed, 100);
+        assert_eq!(plan.folders, vec![fs.lookup("/a").unwrap()]);
+    }
 }
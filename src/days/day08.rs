@@ -1,5 +1,8 @@
-use itertools::izip;
-use take_until::TakeUntilExt;
+use std::fmt::Write;
+use std::ops::{Index, IndexMut};
+use std::path::Path;
+
+use anyhow::bail;
 
 pub fn day08() {
     let input = include_str!("../../input/08.txt");
@@ -9,159 +12,349 @@ pub fn day08() {
     day08b(&grid);
 }
 
-fn day08a(grid: &[Vec<u32>]) {
-    let left_right = left_right(grid);
-    let right_left = right_left(grid);
-    let top_down = top_down(grid);
-    let bottom_up = bottom_up(grid);
+/// Renders the forest shaded by `shading` (`height`, `visibility` or `scenic`) in the terminal,
+/// or writes it as a `pgm` or `ppm` image to `path`. The best tree house location is highlighted.
+pub fn day08_render(shading: &str, format: &str, path: Option<&Path>) -> anyhow::Result<()> {
+    let input = include_str!("../../input/08.txt");
+    let grid = parse_grid(input);
+    let shading = match shading {
+        "height" => Shading::Height,
+        "visibility" => Shading::Visibility,
+        "scenic" => Shading::Scenic,
+        _ => bail!("Unknown shading {shading}, expected height, visibility or scenic"),
+    };
+    let output = match format {
+        "ansi" => render_ansi(&grid, shading),
+        "pgm" => render_pgm(&grid, shading),
+        "ppm" => render_ppm(&grid, shading),
+        _ => bail!("Unknown format {format}, expected ansi, pgm or ppm"),
+    };
+    match path {
+        Some(path) => std::fs::write(path, output)?,
+        None => print!("{output}"),
+    }
+    Ok(())
+}
 
-    let all = or(left_right, right_left, top_down, bottom_up);
-    let count: usize = all
+fn day08a(grid: &Grid<u32>) {
+    let count = visibility(grid)
+        .cells
         .iter()
-        .map(|row| row.iter().filter(|x| **x).count())
-        .sum();
+        .filter(|sides| !sides.is_empty())
+        .count();
 
     println!("Day08a: {count}");
 }
 
-fn day08b(grid: &[Vec<u32>]) {
-    let max = grid
-        .iter()
-        .enumerate()
-        .flat_map(|(i, v)| v.iter().enumerate().map(move |(j, _)| (i, j)))
-        .map(|(i, j)| scenic_score(i, j, grid))
-        .max()
-        .unwrap();
+fn day08b(grid: &Grid<u32>) {
+    let max = scenic_scores(grid).cells.into_iter().max().unwrap();
 
     println!("Day08b: {max}");
 }
 
-fn parse_grid(input: &str) -> Vec<Vec<u32>> {
-    input.lines().map(to_digit_arr).collect()
+fn parse_grid(input: &str) -> Grid<u32> {
+    input.lines().map(to_digit_arr).collect::<Vec<_>>().into()
 }
 
 fn to_digit_arr(line: &str) -> Vec<u32> {
     line.chars().flat_map(|c| c.to_digit(10)).collect()
 }
 
-fn left_right(grid: &[Vec<u32>]) -> Vec<Vec<bool>> {
-    let height = grid.len();
-    let width = grid[0].len();
-    let mut visible = vec![vec![false; width]; height];
-
-    for i in 0..height {
-        let mut max = 0;
-        for j in 0..width {
-            if grid[i][j] > max || j == 0 {
-                max = grid[i][j];
-                visible[i][j] = true;
-            }
+/// A rectangular grid stored row by row and indexed by `(row, column)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |i| (0..width).map(move |j| (i, j)))
+    }
+
+    fn map<U>(&self, f: impl Fn(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
         }
     }
 
-    visible
+    /// Walks from `start` in steps of `step` until leaving the grid.
+    fn line(
+        &self,
+        start: (usize, usize),
+        step: (isize, isize),
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let (width, height) = (self.width, self.height);
+        let start = Some(start).filter(|(i, j)| *i < height && *j < width);
+        std::iter::successors(start, move |(i, j)| {
+            let i = i.checked_add_signed(step.0).filter(|i| *i < height)?;
+            let j = j.checked_add_signed(step.1).filter(|j| *j < width)?;
+            Some((i, j))
+        })
+    }
+
+    /// Every row or column as seen from `side`, starting with the tree closest to that side.
+    fn lines_from(
+        &self,
+        side: Side,
+    ) -> impl Iterator<Item = impl Iterator<Item = (usize, usize)> + '_> {
+        let (width, height) = (self.width, self.height);
+        let (count, step) = match side {
+            Side::Left => (height, (0, 1)),
+            Side::Right => (height, (0, -1)),
+            Side::Top => (width, (1, 0)),
+            Side::Bottom => (width, (-1, 0)),
+        };
+        (0..count).map(move |k| {
+            let start = match side {
+                Side::Left => (k, 0),
+                Side::Right => (k, width.saturating_sub(1)),
+                Side::Top => (0, k),
+                Side::Bottom => (height.saturating_sub(1), k),
+            };
+            self.line(start, step)
+        })
+    }
+
+    fn rows(&self) -> Vec<Vec<T>>
+    where
+        T: Clone,
+    {
+        self.cells
+            .chunks(self.width.max(1))
+            .map(|row| row.to_vec())
+            .collect()
+    }
 }
 
-fn right_left(grid: &[Vec<u32>]) -> Vec<Vec<bool>> {
-    let height = grid.len();
-    let width = grid[0].len();
-    let mut visible = vec![vec![false; width]; height];
-
-    for i in 0..height {
-        let mut max = 0;
-        for j in (0..width).rev() {
-            if grid[i][j] > max || j == width - 1 {
-                max = grid[i][j];
-                visible[i][j] = true;
-            }
+impl<T> From<Vec<Vec<T>>> for Grid<T> {
+    fn from(rows: Vec<Vec<T>>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        let cells: Vec<T> = rows.into_iter().flatten().collect();
+        assert_eq!(cells.len(), width * height, "Grid rows differ in length");
+        Grid {
+            width,
+            height,
+            cells,
         }
     }
+}
 
-    visible
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        &self.cells[i * self.width + j]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut Self::Output {
+        &mut self.cells[i * self.width + j]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+const SIDES: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+/// The sides of the grid a tree is visible from, as a bit set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Sides(u8);
+
+impl Sides {
+    fn insert(&mut self, side: Side) {
+        self.0 |= 1 << side as u8;
+    }
+
+    fn contains(&self, side: Side) -> bool {
+        self.0 & (1 << side as u8) != 0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn iter(&self) -> impl Iterator<Item = Side> + '_ {
+        SIDES.into_iter().filter(|side| self.contains(*side))
+    }
 }
 
-fn top_down(grid: &[Vec<u32>]) -> Vec<Vec<bool>> {
-    let height = grid.len();
-    let width = grid[0].len();
-    let mut visible = vec![vec![false; width]; height];
-
-    for j in 0..width {
-        let mut max = 0;
-        for i in 0..height {
-            if grid[i][j] > max || i == 0 {
-                max = grid[i][j];
-                visible[i][j] = true;
+/// Marks for every tree the sides it is visible from, walking every row and column once from
+/// each side and keeping track of the tallest tree so far.
+fn visibility(grid: &Grid<u32>) -> Grid<Sides> {
+    let mut visible = grid.map(|_| Sides::default());
+    for side in SIDES {
+        for line in grid.lines_from(side) {
+            let mut max = None;
+            for pos in line {
+                if max.is_none_or(|max| grid[pos] > max) {
+                    max = Some(grid[pos]);
+                    visible[pos].insert(side);
+                }
             }
         }
     }
-
     visible
 }
 
-fn bottom_up(grid: &[Vec<u32>]) -> Vec<Vec<bool>> {
-    let height = grid.len();
-    let width = grid[0].len();
-    let mut visible = vec![vec![false; width]; height];
-
-    for j in 0..width {
-        let mut max = 0;
-        for i in (0..height).rev() {
-            let val = grid[i][j];
-            if val > max || i == height - 1 {
-                max = val;
-                visible[i][j] = true;
+/// How many trees can be seen from every tree when looking towards `side`. Walks every line
+/// away from `side` with a stack of the trees that can still block the view, which only holds
+/// trees that are taller than every tree after them.
+fn viewing_distances(grid: &Grid<u32>, side: Side) -> Grid<usize> {
+    let mut distances = grid.map(|_| 0);
+    let mut stack: Vec<(usize, u32)> = vec![];
+    for line in grid.lines_from(side) {
+        stack.clear();
+        for (k, pos) in line.enumerate() {
+            let height = grid[pos];
+            while stack.last().is_some_and(|(_, h)| *h < height) {
+                stack.pop();
             }
+            distances[pos] = match stack.last() {
+                Some((blocking, _)) => k - blocking,
+                None => k,
+            };
+            stack.push((k, height));
         }
     }
+    distances
+}
 
-    visible
+fn scenic_scores(grid: &Grid<u32>) -> Grid<usize> {
+    let mut scores = grid.map(|_| 1);
+    for side in SIDES {
+        let distances = viewing_distances(grid, side);
+        for (score, distance) in scores.cells.iter_mut().zip(distances.cells) {
+            *score *= distance;
+        }
+    }
+    scores
 }
 
-fn or(
-    g1: Vec<Vec<bool>>,
-    g2: Vec<Vec<bool>>,
-    g3: Vec<Vec<bool>>,
-    g4: Vec<Vec<bool>>,
-) -> Vec<Vec<bool>> {
-    izip!(g1, g2, g3, g4)
-        .map(|(row1, row2, row3, row4)| {
-            izip!(row1, row2, row3, row4)
-                .map(|(b1, b2, b3, b4)| b1 || b2 || b3 || b4)
-                .collect::<Vec<_>>()
-        })
-        .collect()
+/// The `k` trees with the highest scenic scores, best first.
+fn best_sites(scores: &Grid<usize>, k: usize) -> Vec<((usize, usize), usize)> {
+    let mut sites: Vec<_> = scores.positions().map(|pos| (pos, scores[pos])).collect();
+    sites.sort_by_key(|(pos, score)| (std::cmp::Reverse(*score), *pos));
+    sites.truncate(k);
+    sites
 }
 
-fn scenic_score(i: usize, j: usize, grid: &[Vec<u32>]) -> usize {
-    let height = grid[i][j];
-    let up = (0..i)
-        .rev()
-        .map(|i| grid[i][j])
-        .take_until(|h| *h >= height)
-        .count();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shading {
+    Height,
+    /// By the number of sides a tree is visible from
+    Visibility,
+    /// Logarithmic in the scenic score, so the few good spots don't drown out everything else
+    Scenic,
+}
 
-    let down = (i + 1..grid.len())
-        .map(|i| grid[i][j])
-        .take_until(|h| *h >= height)
-        .count();
+/// Brightness of every tree between 0 and 1.
+fn shade(grid: &Grid<u32>, shading: Shading) -> Grid<f64> {
+    match shading {
+        Shading::Height => grid.map(|h| *h as f64 / 9.0),
+        Shading::Visibility => visibility(grid).map(|sides| sides.iter().count() as f64 / 4.0),
+        Shading::Scenic => {
+            let scores = scenic_scores(grid);
+            let max = scores.cells.iter().copied().max().unwrap_or(0);
+            let scale = (1.0 + max as f64).ln().max(f64::EPSILON);
+            scores.map(|score| (1.0 + *score as f64).ln() / scale)
+        }
+    }
+}
 
-    let left = (0..j)
-        .rev()
-        .map(|j| grid[i][j])
-        .take_until(|h| *h >= height)
-        .count();
+const HIGHLIGHT: (u8, u8, u8) = (255, 40, 40);
 
-    let right = (j + 1..grid[i].len())
-        .map(|j| grid[i][j])
-        .take_until(|h| *h >= height)
-        .count();
+/// Blends from a dark brown for 0 to a bright green for 1.
+fn color(value: f64) -> (u8, u8, u8) {
+    let blend = |from: f64, to: f64| (from + (to - from) * value.clamp(0.0, 1.0)).round() as u8;
+    (blend(40.0, 110.0), blend(26.0, 230.0), blend(13.0, 60.0))
+}
 
-    up * down * right * left
+fn best_site(grid: &Grid<u32>) -> Option<(usize, usize)> {
+    best_sites(&scenic_scores(grid), 1)
+        .first()
+        .map(|(pos, _)| *pos)
+}
+
+/// The heights as digits on coloured backgrounds, with the best site in red.
+fn render_ansi(grid: &Grid<u32>, shading: Shading) -> String {
+    let shades = shade(grid, shading);
+    let best = best_site(grid);
+    let mut out = String::new();
+    for i in 0..grid.height {
+        for j in 0..grid.width {
+            let (r, g, b) = if Some((i, j)) == best {
+                HIGHLIGHT
+            } else {
+                color(shades[(i, j)])
+            };
+            write!(out, "\x1b[48;2;{r};{g};{b}m{}", grid[(i, j)]).unwrap();
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// A plain grayscale PGM image with one pixel per tree. The best site is left white.
+fn render_pgm(grid: &Grid<u32>, shading: Shading) -> String {
+    let shades = shade(grid, shading);
+    let best = best_site(grid);
+    let mut out = format!("P2\n{} {}\n255\n", grid.width, grid.height);
+    for i in 0..grid.height {
+        let row: Vec<_> = (0..grid.width)
+            .map(|j| match Some((i, j)) == best {
+                true => 255,
+                false => (shades[(i, j)] * 254.0).round() as u8,
+            })
+            .map(|value| value.to_string())
+            .collect();
+        out.push_str(&row.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// A plain PPM image with one pixel per tree, coloured like the terminal output.
+fn render_ppm(grid: &Grid<u32>, shading: Shading) -> String {
+    let shades = shade(grid, shading);
+    let best = best_site(grid);
+    let mut out = format!("P3\n{} {}\n255\n", grid.width, grid.height);
+    for i in 0..grid.height {
+        let row: Vec<_> = (0..grid.width)
+            .map(|j| match Some((i, j)) == best {
+                true => HIGHLIGHT,
+                false => color(shades[(i, j)]),
+            })
+            .map(|(r, g, b)| format!("{r} {g} {b}"))
+            .collect();
+        out.push_str(&row.join("  "));
+        out.push('\n');
+    }
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use take_until::TakeUntilExt;
+
+    fn visible_from(grid: &[Vec<u32>], side: Side) -> Vec<Vec<bool>> {
+        visibility(&grid.to_vec().into())
+            .map(|sides| sides.contains(side))
+            .rows()
+    }
 
     #[test]
     fn test_left_right() {
@@ -171,7 +364,7 @@ mod tests {
             vec![true, true, true],
             vec![true, true, true],
         ];
-        assert_eq!(left_right(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Left), expected);
 
         let grid = vec![vec![3, 2, 1], vec![6, 5, 4], vec![9, 8, 7]];
         let expected = vec![
@@ -179,7 +372,7 @@ mod tests {
             vec![true, false, false],
             vec![true, false, false],
         ];
-        assert_eq!(left_right(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Left), expected);
 
         let grid = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 1, 1]];
         let expected = vec![
@@ -187,7 +380,7 @@ mod tests {
             vec![true, false, false],
             vec![true, false, false],
         ];
-        assert_eq!(left_right(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Left), expected);
     }
 
     #[test]
@@ -198,7 +391,7 @@ mod tests {
             vec![false, false, true],
             vec![false, false, true],
         ];
-        assert_eq!(right_left(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Right), expected);
 
         let grid = vec![vec![3, 2, 1], vec![6, 5, 4], vec![9, 8, 7]];
         let expected = vec![
@@ -206,7 +399,7 @@ mod tests {
             vec![true, true, true],
             vec![true, true, true],
         ];
-        assert_eq!(right_left(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Right), expected);
 
         let grid = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 1, 1]];
         let expected = vec![
@@ -214,7 +407,7 @@ mod tests {
             vec![false, false, true],
             vec![false, false, true],
         ];
-        assert_eq!(right_left(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Right), expected);
     }
 
     #[test]
@@ -225,7 +418,7 @@ mod tests {
             vec![true, true, true],
             vec![true, true, true],
         ];
-        assert_eq!(top_down(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Top), expected);
 
         let grid = vec![vec![3, 2, 1], vec![6, 5, 4], vec![9, 8, 7]];
         let expected = vec![
@@ -233,7 +426,7 @@ mod tests {
             vec![true, true, true],
             vec![true, true, true],
         ];
-        assert_eq!(top_down(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Top), expected);
 
         let grid = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 1, 1]];
         let expected = vec![
@@ -241,7 +434,7 @@ mod tests {
             vec![false, false, false],
             vec![false, false, false],
         ];
-        assert_eq!(top_down(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Top), expected);
 
         let grid = vec![vec![3, 1, 1], vec![2, 1, 1], vec![1, 1, 2]];
         let expected = vec![
@@ -249,7 +442,7 @@ mod tests {
             vec![false, false, false],
             vec![false, false, true],
         ];
-        assert_eq!(top_down(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Top), expected);
     }
 
     #[test]
@@ -260,7 +453,7 @@ mod tests {
             vec![false, false, false],
             vec![true, true, true],
         ];
-        assert_eq!(bottom_up(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Bottom), expected);
 
         let grid = vec![vec![3, 2, 1], vec![6, 5, 4], vec![9, 8, 7]];
         let expected = vec![
@@ -268,7 +461,7 @@ mod tests {
             vec![false, false, false],
             vec![true, true, true],
         ];
-        assert_eq!(bottom_up(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Bottom), expected);
 
         let grid = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 1, 1]];
         let expected = vec![
@@ -276,7 +469,7 @@ mod tests {
             vec![false, false, false],
             vec![true, true, true],
         ];
-        assert_eq!(bottom_up(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Bottom), expected);
 
         let grid = vec![vec![3, 1, 1], vec![2, 1, 1], vec![1, 1, 2]];
         let expected = vec![
@@ -284,7 +477,7 @@ mod tests {
             vec![true, false, false],
             vec![true, true, true],
         ];
-        assert_eq!(bottom_up(&grid), expected);
+        assert_eq!(visible_from(&grid, Side::Bottom), expected);
     }
 
     #[test]
@@ -296,7 +489,130 @@ mod tests {
             vec![3, 3, 5, 4, 9],
             vec![3, 5, 3, 9, 0],
         ];
-        assert_eq!(scenic_score(1, 2, &grid), 4);
-        assert_eq!(scenic_score(3, 2, &grid), 8);
+        let grid = grid.into();
+        let scores = scenic_scores(&grid);
+        assert_eq!(scores[(1, 2)], 4);
+        assert_eq!(scores[(3, 2)], 8);
+    }
+
+    #[test]
+    fn test_scenic_scores_match_naive() {
+        let mut state: u32 = 8;
+        let rows: Vec<Vec<u32>> = (0..17)
+            .map(|_| {
+                (0..23)
+                    .map(|_| {
+                        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                        (state >> 16) % 10
+                    })
+                    .collect()
+            })
+            .collect();
+        let grid: Grid<u32> = rows.into();
+        let scores = scenic_scores(&grid);
+        for (i, j) in grid.positions() {
+            assert_eq!(scores[(i, j)], naive_scenic_score(i, j, &grid), "{i} {j}");
+        }
+    }
+
+    #[test]
+    fn test_best_sites() {
+        let grid = parse_grid("30373\n25512\n65332\n33549\n35390\n");
+        let scores = scenic_scores(&grid);
+        assert_eq!(best_sites(&scores, 2), vec![((3, 2), 8), ((2, 1), 6)]);
+        assert_eq!(best_sites(&scores, 100).len(), 25);
+    }
+
+    #[test]
+    fn test_shade() {
+        let grid = parse_grid("30373\n25512\n65332\n33549\n35390\n");
+        assert_eq!(shade(&grid, Shading::Height)[(3, 4)], 1.0);
+        assert_eq!(shade(&grid, Shading::Visibility)[(2, 2)], 0.0);
+        assert_eq!(shade(&grid, Shading::Visibility)[(0, 0)], 0.5);
+        assert_eq!(shade(&grid, Shading::Scenic)[(3, 2)], 1.0);
+        assert_eq!(shade(&grid, Shading::Scenic)[(0, 0)], 0.0);
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let grid = parse_grid("123\n456\n789\n");
+        let out = render_ansi(&grid, Shading::Height);
+        assert_eq!(out.lines().count(), 3);
+        let (r, g, b) = color(1.0 / 9.0);
+        assert!(
+            out.starts_with(&format!("\x1b[48;2;{r};{g};{b}m1")),
+            "{out:?}"
+        );
+        assert!(out.contains("\x1b[48;2;255;40;40m5"), "{out:?}");
+        assert!(out.ends_with("9\x1b[0m\n"), "{out:?}");
+    }
+
+    #[test]
+    fn test_render_images() {
+        let grid = parse_grid("30373\n25512\n65332\n33549\n35390\n");
+        let pgm = render_pgm(&grid, Shading::Height);
+        let mut lines = pgm.lines();
+        assert_eq!(lines.next(), Some("P2"));
+        assert_eq!(lines.next(), Some("5 5"));
+        assert_eq!(lines.next(), Some("255"));
+        assert_eq!(lines.nth(3), Some("85 85 255 113 254"));
+
+        let ppm = render_ppm(&grid, Shading::Scenic);
+        let values: Vec<u32> = ppm
+            .split_whitespace()
+            .skip(1)
+            .map(|value| value.parse().unwrap())
+            .collect();
+        assert_eq!(values[..3], [5, 5, 255]);
+        assert_eq!(values.len(), 3 + 5 * 5 * 3);
+        let best = 3 + (3 * 5 + 2) * 3;
+        assert_eq!(values[best..best + 3], [255, 40, 40]);
+    }
+
+    fn naive_scenic_score(i: usize, j: usize, grid: &Grid<u32>) -> usize {
+        let height = grid[(i, j)];
+        SIDES
+            .into_iter()
+            .map(|side| {
+                let step = match side {
+                    Side::Left => (0, -1),
+                    Side::Right => (0, 1),
+                    Side::Top => (-1, 0),
+                    Side::Bottom => (1, 0),
+                };
+                grid.line((i, j), step)
+                    .skip(1)
+                    .map(|pos| grid[pos])
+                    .take_until(|h| *h >= height)
+                    .count()
+            })
+            .product()
+    }
+
+    #[test]
+    fn test_visibility_sides() {
+        let grid = parse_grid("30373\n25512\n65332\n33549\n35390\n");
+        let visible = visibility(&grid);
+        assert_eq!(
+            visible
+                .cells
+                .iter()
+                .filter(|sides| !sides.is_empty())
+                .count(),
+            21
+        );
+        assert_eq!(
+            visible[(1, 1)].iter().collect::<Vec<_>>(),
+            vec![Side::Left, Side::Top]
+        );
+        assert_eq!(
+            visible[(1, 2)].iter().collect::<Vec<_>>(),
+            vec![Side::Right, Side::Top]
+        );
+        assert!(visible[(2, 2)].is_empty());
+        assert_eq!(
+            visible[(0, 0)].iter().collect::<Vec<_>>(),
+            vec![Side::Left, Side::Top]
+        );
     }
 }
@@ -1,5 +1,11 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
 use anyhow::anyhow;
-use itertools::{repeat_n, Itertools};
+use itertools::Itertools;
 
 #[allow(unused)]
 pub fn day09() {
@@ -9,100 +15,302 @@ pub fn day09() {
     day09b(input);
 }
 
-fn day09a(input: &str) {
-    let positions = head_positions(input)
-        .scan((0, 0), |tail_pos, head_pos| {
-            *tail_pos = new_tail_pos(tail_pos, head_pos);
-            Some(*tail_pos)
-        })
-        .unique()
-        .count();
+const EXAMPLE: &str = "R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20";
 
-    println!("Day09a: {positions:?}")
+/// Plays the rope with `knots` knots moving through the motions in the file `input`, or through
+/// the larger example, in the terminal.
+pub fn day09_play(knots: usize, delay: Duration, input: Option<&Path>) {
+    match read_motions(input).and_then(|input| Recording::record(&input, knots)) {
+        Ok(recording) => recording.play(delay),
+        Err(e) => println!("{e}"),
+    }
+}
+
+/// Writes every frame of the rope with `knots` knots moving through the motions in the file
+/// `input`, or through the larger example, to `path`.
+pub fn day09_dump(knots: usize, path: &Path, input: Option<&Path>) {
+    let result = read_motions(input)
+        .and_then(|input| Recording::record(&input, knots))
+        .and_then(|recording| recording.dump(path));
+    if let Err(e) = result {
+        println!("{e}");
+    }
+}
+
+fn read_motions(input: Option<&Path>) -> anyhow::Result<String> {
+    match input {
+        Some(input) => Ok(std::fs::read_to_string(input)?),
+        None => Ok(EXAMPLE.to_string()),
+    }
+}
+
+fn day09a(input: &str) {
+    match simulate(input, 2) {
+        Ok(rope) => println!("Day09a: {:?}", rope.visited_count(1).unwrap()),
+        Err(e) => println!("Day09a: {e}"),
+    }
 }
 
 fn day09b(input: &str) {
-    let head_positions = head_positions(input).collect_vec();
-    let positions = (0..9).fold(head_positions, |mut acc, _| {
-        // println!("{acc:?}\n");
-        acc = acc
+    match simulate(input, 10) {
+        Ok(rope) => println!("Day09b: {}", rope.visited_count(9).unwrap()),
+        Err(e) => println!("Day09b: {e}"),
+    }
+}
+
+fn simulate(input: &str, knots: usize) -> anyhow::Result<Rope> {
+    let mut rope = Rope::new(knots);
+    for line in input.lines() {
+        for delta in expand_command(parse_line(line)?) {
+            rope.move_head(delta)?;
+        }
+    }
+    Ok(rope)
+}
+
+/// How knots follow the knot in front of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FollowRule {
+    /// How far a knot can be from the one in front of it, in both directions, without moving
+    slack: u32,
+    /// Whether a knot can move more than one cell per tick to get back within `slack`
+    catch_up: bool,
+}
+
+impl Default for FollowRule {
+    fn default() -> Self {
+        FollowRule {
+            slack: 1,
+            catch_up: true,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum RopeError {
+    #[error(
+        "Knot {knot} at {follower:?} cannot keep up with the knot in front of it at {leader:?}"
+    )]
+    TooFar {
+        knot: usize,
+        leader: (i32, i32),
+        follower: (i32, i32),
+    },
+}
+
+/// A rope of knots where every knot follows the one before it, starting with the head.
+struct Rope {
+    knots: Vec<(i32, i32)>,
+    /// Positions visited by each knot, for the knots that are tracked
+    visited: Vec<Option<HashSet<(i32, i32)>>>,
+    rule: FollowRule,
+}
+
+impl Rope {
+    /// A rope with `len` knots at the origin, tracking the positions of all of them.
+    fn new(len: usize) -> Self {
+        Self::tracking(len, &(0..len).collect_vec())
+    }
+
+    /// A rope with `len` knots at the origin, tracking the positions of the `tracked` knots.
+    fn tracking(len: usize, tracked: &[usize]) -> Self {
+        let mut visited = vec![None; len];
+        for knot in tracked {
+            visited[*knot] = Some(HashSet::from([(0, 0)]));
+        }
+        Rope {
+            knots: vec![(0, 0); len],
+            visited,
+            rule: FollowRule::default(),
+        }
+    }
+
+    fn with_rule(mut self, rule: FollowRule) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    fn step(&mut self, direction: Direction) -> Result<(), RopeError> {
+        self.move_head(direction.delta())
+    }
+
+    /// Moves the head to `position` in a single tick.
+    fn move_head_to(&mut self, position: (i32, i32)) -> Result<(), RopeError> {
+        let Some(head) = self.knots.first() else {
+            return Ok(());
+        };
+        self.move_head((position.0 - head.0, position.1 - head.1))
+    }
+
+    /// Moves the head by `delta` in a single tick and lets all other knots follow. Once a knot
+    /// stays in place, the knots behind it do as well, so they are not looked at. If a knot cannot
+    /// keep up, the rope is left as it was.
+    fn move_head(&mut self, delta: (i32, i32)) -> Result<(), RopeError> {
+        let Some(head) = self.knots.first() else {
+            return Ok(());
+        };
+        // Every position a knot passes through, only applied once the whole rope has moved
+        let mut moves = vec![(0, (head.0 + delta.0, head.1 + delta.1))];
+        let mut leader = moves[0].1;
+        for i in 1..self.knots.len() {
+            let start = self.knots[i];
+            let mut knot = start;
+            while !within(knot, leader, self.rule.slack) {
+                knot = step_towards(knot, leader);
+                moves.push((i, knot));
+                if !self.rule.catch_up && !within(knot, leader, self.rule.slack) {
+                    return Err(RopeError::TooFar {
+                        knot: i,
+                        leader,
+                        follower: knot,
+                    });
+                }
+            }
+            if knot == start {
+                break;
+            }
+            leader = knot;
+        }
+
+        for (knot, position) in moves {
+            self.knots[knot] = position;
+            self.record(knot);
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, knot: usize) {
+        if let Some(visited) = &mut self.visited[knot] {
+            visited.insert(self.knots[knot]);
+        }
+    }
+
+    /// The positions visited by `knot`, if it is tracked.
+    fn visited(&self, knot: usize) -> Option<&HashSet<(i32, i32)>> {
+        self.visited.get(knot)?.as_ref()
+    }
+
+    fn visited_count(&self, knot: usize) -> Option<usize> {
+        self.visited(knot).map(HashSet::len)
+    }
+
+    /// The number of visited positions of every tracked knot.
+    fn visited_counts(&self) -> Vec<(usize, usize)> {
+        (0..self.knots.len())
+            .filter_map(|knot| Some((knot, self.visited_count(knot)?)))
+            .collect()
+    }
+}
+
+/// The positions of all knots after every tick, for drawing the rope.
+struct Recording {
+    frames: Vec<Vec<(i32, i32)>>,
+}
+
+/// The smallest and largest row and column of any knot in a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    min: (i32, i32),
+    max: (i32, i32),
+}
+
+impl Recording {
+    fn record(input: &str, knots: usize) -> anyhow::Result<Self> {
+        let mut rope = Rope::tracking(knots, &[]);
+        let mut frames = vec![rope.knots.clone()];
+        for line in input.lines() {
+            for delta in expand_command(parse_line(line)?) {
+                rope.move_head(delta)?;
+                frames.push(rope.knots.clone());
+            }
+        }
+        Ok(Recording { frames })
+    }
+
+    fn bounds(&self) -> Bounds {
+        let positions = self.frames.iter().flatten().chain([&(0, 0)]);
+        let (rows, columns): (Vec<_>, Vec<_>) = positions.copied().unzip();
+        Bounds {
+            min: (*rows.iter().min().unwrap(), *columns.iter().min().unwrap()),
+            max: (*rows.iter().max().unwrap(), *columns.iter().max().unwrap()),
+        }
+    }
+
+    /// Draws every frame on the same grid, with the head as `H`, the other knots by their number,
+    /// the start as `s` and the cells the tail has visited so far as `#`.
+    fn frames(&self) -> Vec<String> {
+        let bounds = self.bounds();
+        let mut trail = HashSet::new();
+        self.frames
             .iter()
-            .cloned()
-            .scan((0, 0), |tail_pos, head_pos| {
-                *tail_pos = new_tail_pos(tail_pos, head_pos);
-                Some(*tail_pos)
+            .enumerate()
+            .map(|(tick, knots)| {
+                trail.extend(knots.last());
+                format!(
+                    "Tick {tick}/{}\n{}",
+                    self.frames.len() - 1,
+                    draw(knots, &trail, bounds)
+                )
             })
-            .collect_vec();
-
-        acc
-    });
-    // println!("{positions:?}");
-    let count = positions.iter().unique().count();
-    println!("Day09b: {count}");
-}
-
-fn new_tail_pos(tail_pos: &(i32, i32), head_pos: (i32, i32)) -> (i32, i32) {
-    let tail_pos = *tail_pos;
-    let offset_height = head_pos.0 - tail_pos.0;
-    let offset_width = head_pos.1 - tail_pos.1;
-    match (offset_height, offset_width) {
-        (0, 0) => tail_pos,
-
-        (1, 0) => tail_pos,
-        (0, 1) => tail_pos,
-        (-1, 0) => tail_pos,
-        (0, -1) => tail_pos,
-        (1, 1) => tail_pos,
-        (-1, 1) => tail_pos,
-        (1, -1) => tail_pos,
-        (-1, -1) => tail_pos,
-
-        (2, 0) => (tail_pos.0 + 1, tail_pos.1),
-        (2, 1) => (tail_pos.0 + 1, tail_pos.1 + 1),
-        (2, -1) => (tail_pos.0 + 1, tail_pos.1 - 1),
-
-        (-2, 0) => (tail_pos.0 - 1, tail_pos.1),
-        (-2, 1) => (tail_pos.0 - 1, tail_pos.1 + 1),
-        (-2, -1) => (tail_pos.0 - 1, tail_pos.1 - 1),
-
-        (0, 2) => (tail_pos.0, tail_pos.1 + 1),
-        (1, 2) => (tail_pos.0 + 1, tail_pos.1 + 1),
-        (-1, 2) => (tail_pos.0 - 1, tail_pos.1 + 1),
-
-        (0, -2) => (tail_pos.0, tail_pos.1 - 1),
-        (1, -2) => (tail_pos.0 + 1, tail_pos.1 - 1),
-        (-1, -2) => (tail_pos.0 - 1, tail_pos.1 - 1),
-
-        (2, 2) => (tail_pos.0 + 1, tail_pos.1 + 1),
-        (-2, 2) => (tail_pos.0 - 1, tail_pos.1 + 1),
-        (2, -2) => (tail_pos.0 + 1, tail_pos.1 - 1),
-        (-2, -2) => (tail_pos.0 - 1, tail_pos.1 - 1),
-
-        _ => unreachable!("Difference too large. Head: {head_pos:?}, Tail: {tail_pos:?}"),
-    }
-}
-
-fn head_positions(input: &str) -> impl Iterator<Item = (i32, i32)> + '_ {
-    input
-        .lines()
-        .map(|line| parse_line(line).unwrap())
-        .flat_map(expand_command)
-        .scan((0, 0), |state, direction| {
-            match direction {
-                Direction::U => state.0 += 1,
-                Direction::D => state.0 -= 1,
-                Direction::R => state.1 += 1,
-                Direction::L => state.1 -= 1,
-            };
-
-            Some(state.to_owned())
+            .collect()
+    }
+
+    fn play(&self, delay: Duration) {
+        for frame in self.frames() {
+            println!("\x1b[2J\x1b[H{frame}");
+            std::io::stdout().flush().unwrap();
+            thread::sleep(delay);
+        }
+    }
+
+    fn dump(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, self.frames().join("\n\n"))?;
+        Ok(())
+    }
+}
+
+/// Draws the knots and the trail within `bounds`, with up at the top. Knots in front cover the
+/// ones behind them.
+fn draw(knots: &[(i32, i32)], trail: &HashSet<(i32, i32)>, bounds: Bounds) -> String {
+    (bounds.min.0..=bounds.max.0)
+        .rev()
+        .map(|row| {
+            (bounds.min.1..=bounds.max.1)
+                .map(|column| {
+                    let position = (row, column);
+                    match knots.iter().position(|knot| *knot == position) {
+                        Some(0) => 'H',
+                        Some(knot) => char::from_digit(knot as u32 % 36, 36).unwrap(),
+                        None if position == (0, 0) => 's',
+                        None if trail.contains(&position) => '#',
+                        None => '.',
+                    }
+                })
+                .collect::<String>()
         })
+        .join("\n")
+}
+
+/// Whether `a` and `b` are at most `slack` apart in both directions.
+fn within(a: (i32, i32), b: (i32, i32), slack: u32) -> bool {
+    a.0.abs_diff(b.0) <= slack && a.1.abs_diff(b.1) <= slack
+}
+
+/// Moves one step from `from` towards `to`, diagonally if they are in neither the same row nor
+/// the same column.
+fn step_towards(from: (i32, i32), to: (i32, i32)) -> (i32, i32) {
+    (
+        from.0 + (to.0 - from.0).signum(),
+        from.1 + (to.1 - from.1).signum(),
+    )
 }
 
+/// Moves the head `length` cells into `direction`, `speed` cells per tick.
 #[derive(Debug, Eq, PartialEq)]
 struct Command {
     direction: Direction,
     length: usize,
+    speed: usize,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -111,10 +319,30 @@ enum Direction {
     U,
     L,
     D,
+    UR,
+    UL,
+    DR,
+    DL,
 }
 
+impl Direction {
+    fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::U => (1, 0),
+            Direction::D => (-1, 0),
+            Direction::R => (0, 1),
+            Direction::L => (0, -1),
+            Direction::UR => (1, 1),
+            Direction::UL => (1, -1),
+            Direction::DR => (-1, 1),
+            Direction::DL => (-1, -1),
+        }
+    }
+}
+
+/// Parses `<direction> <length>`, optionally followed by the number of cells to move per tick.
 fn parse_line(line: &str) -> anyhow::Result<Command> {
-    let (direction, length) = line
+    let (direction, rest) = line
         .split_once(' ')
         .ok_or(anyhow!("Malformated Command: {line}"))?;
     let direction = match direction {
@@ -122,14 +350,34 @@ fn parse_line(line: &str) -> anyhow::Result<Command> {
         "D" => Direction::D,
         "L" => Direction::L,
         "R" => Direction::R,
+        "UR" | "RU" => Direction::UR,
+        "UL" | "LU" => Direction::UL,
+        "DR" | "RD" => Direction::DR,
+        "DL" | "LD" => Direction::DL,
         _ => return Err(anyhow!("Cannot Parse Direction: {direction}")),
     };
-    let length = length.parse()?;
-    Ok(Command { direction, length })
+    let (length, speed) = match rest.split_once(' ') {
+        Some((length, speed)) => (length.parse()?, speed.parse()?),
+        None => (rest.parse()?, 1),
+    };
+    if speed == 0 {
+        return Err(anyhow!("Speed must be positive: {line}"));
+    }
+    Ok(Command {
+        direction,
+        length,
+        speed,
+    })
 }
 
-fn expand_command(command: Command) -> impl Iterator<Item = Direction> {
-    repeat_n(command.direction, command.length)
+/// The head movement of every tick of the command.
+fn expand_command(command: Command) -> impl Iterator<Item = (i32, i32)> {
+    let (dy, dx) = command.direction.delta();
+    let ticks = command.length.div_ceil(command.speed);
+    (0..ticks).map(move |tick| {
+        let cells = command.speed.min(command.length - tick * command.speed) as i32;
+        (dy * cells, dx * cells)
+    })
 }
 
 #[cfg(test)]
@@ -181,22 +429,161 @@ mod tests {
 
     #[test]
     fn test_head_positions() {
-        let input = "U 2\nR 3\nD 1";
+        let mut rope = Rope::new(1);
+        let mut positions = vec![];
+        for line in ["U 2", "R 3", "D 1"] {
+            for delta in expand_command(parse_line(line).unwrap()) {
+                rope.move_head(delta).unwrap();
+                positions.push(rope.knots[0]);
+            }
+        }
         let expected = vec![(1, 0), (2, 0), (2, 1), (2, 2), (2, 3), (1, 3)];
-        assert_eq!(head_positions(input).collect::<Vec<_>>(), expected);
+        assert_eq!(positions, expected);
     }
 
     #[test]
     fn test_move() {
         for i in -2..=2 {
             for j in -2..=2 {
-                let actual = new_tail_pos(&(0, 0), (i, j));
+                let mut rope = Rope::new(2);
+                rope.move_head((i, j)).unwrap();
                 let expected = get_movements(&(i, j), &(0, 0));
-                assert_eq!(expected, actual);
+                assert_eq!(rope.knots[1], expected);
             }
         }
     }
 
+    #[test]
+    fn test_rope() {
+        let input = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+        assert_eq!(simulate(input, 2).unwrap().visited_count(1), Some(13));
+        assert_eq!(simulate(input, 10).unwrap().visited_count(9), Some(1));
+
+        let input = EXAMPLE;
+        let rope = simulate(input, 10).unwrap();
+        assert_eq!(rope.visited_count(9), Some(36));
+        assert_eq!(rope.knots[0], (15, -11));
+    }
+
+    #[test]
+    fn test_rope_tracking() {
+        let mut rope = Rope::tracking(300, &[1, 299]);
+        for _ in 0..500 {
+            rope.step(Direction::R).unwrap();
+        }
+        assert_eq!(rope.visited_count(0), None);
+        assert_eq!(rope.visited_counts(), vec![(1, 500), (299, 202)]);
+        assert!(rope.visited(299).unwrap().contains(&(0, 201)));
+    }
+
+    #[test]
+    fn test_parse_line_extended() {
+        let command = parse_line("UR 3").unwrap();
+        assert_eq!(command.direction, Direction::UR);
+        assert_eq!(command.speed, 1);
+
+        let command = parse_line("DL 10 4").unwrap();
+        assert_eq!(command.direction, Direction::DL);
+        assert_eq!(command.length, 10);
+        assert_eq!(command.speed, 4);
+        assert_eq!(
+            expand_command(command).collect::<Vec<_>>(),
+            vec![(-4, -4), (-4, -4), (-2, -2)]
+        );
+
+        assert!(parse_line("R 3 0").is_err());
+    }
+
+    #[test]
+    fn test_diagonal_head() {
+        let rope = simulate("UR 4\nDR 2", 3).unwrap();
+        assert_eq!(rope.knots, vec![(2, 6), (2, 5), (2, 4)]);
+        assert_eq!(rope.visited_count(2), Some(5));
+    }
+
+    #[test]
+    fn test_teleport_catch_up() {
+        let mut rope = Rope::new(3);
+        rope.move_head_to((0, 5)).unwrap();
+        assert_eq!(rope.knots, vec![(0, 5), (0, 4), (0, 3)]);
+        assert_eq!(rope.visited_count(1), Some(5));
+        assert_eq!(rope.visited_count(2), Some(4));
+
+        rope.move_head_to((-3, 2)).unwrap();
+        assert_eq!(rope.knots, vec![(-3, 2), (-2, 2), (-1, 2)]);
+    }
+
+    #[test]
+    fn test_slack() {
+        let mut rope = Rope::new(2).with_rule(FollowRule {
+            slack: 2,
+            catch_up: true,
+        });
+        for _ in 0..5 {
+            rope.step(Direction::U).unwrap();
+        }
+        assert_eq!(rope.knots, vec![(5, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn test_too_far() {
+        let mut rope = Rope::new(2).with_rule(FollowRule {
+            slack: 1,
+            catch_up: false,
+        });
+        rope.move_head((2, 1)).unwrap();
+        assert_eq!(rope.knots, vec![(2, 1), (1, 1)]);
+
+        let result = rope.move_head((0, 3));
+        assert_eq!(
+            result,
+            Err(RopeError::TooFar {
+                knot: 1,
+                leader: (2, 4),
+                follower: (2, 2)
+            })
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Knot 1 at (2, 2) cannot keep up with the knot in front of it at (2, 4)"
+        );
+        assert_eq!(rope.knots, vec![(2, 1), (1, 1)]);
+        assert_eq!(rope.visited_count(0), Some(2));
+        assert_eq!(rope.visited_count(1), Some(2));
+    }
+
+    #[test]
+    fn test_read_motions() {
+        assert_eq!(read_motions(None).unwrap(), EXAMPLE);
+
+        let path = std::env::temp_dir().join(format!("day09-test-{}", std::process::id()));
+        std::fs::write(&path, "R 4\nU 4\nL 3").unwrap();
+        let input = read_motions(Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Recording::record(&input, 3).unwrap().frames.len(), 12);
+
+        assert!(read_motions(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_recording() {
+        let recording = Recording::record("R 4\nU 4\nL 3", 3).unwrap();
+        assert_eq!(recording.frames.len(), 12);
+        assert_eq!(
+            recording.bounds(),
+            Bounds {
+                min: (0, 0),
+                max: (4, 4)
+            }
+        );
+
+        let frames = recording.frames();
+        assert_eq!(frames.len(), 12);
+        assert_eq!(frames[0], "Tick 0/11\n.....\n.....\n.....\n.....\nH....");
+        assert_eq!(frames[4], "Tick 4/11\n.....\n.....\n.....\n.....\ns#21H");
+        assert_eq!(frames[11], "Tick 11/11\n.H1..\n...2.\n....#\n...#.\ns##..");
+    }
+
     fn get_movements(
         leader_after_move: &(i32, i32),
         follower_before_move: &(i32, i32),
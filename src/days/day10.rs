@@ -1,73 +1,1370 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
 use itertools::Itertools;
-use std::iter;
 
 pub fn day10() {
     let input = include_str!("../../input/10.txt");
-    day10a(input);
-    day10b(input);
+    match parse_program(input) {
+        Ok(program) => {
+            day10a(&program);
+            day10b(&program);
+        }
+        Err(e) => println!("Day10: {e}"),
+    }
+}
+
+/// Debugs the program in the file `program`, reading commands from the file `script` if given and
+/// from stdin otherwise.
+pub fn day10_debug(program: &Path, script: Option<&Path>) -> anyhow::Result<()> {
+    let program = parse_program(&std::fs::read_to_string(program)?)?;
+    let mut debugger = Debugger::new(program);
+    match script {
+        Some(script) => debugger.repl(BufReader::new(File::open(script)?), io::stdout(), false)?,
+        None => debugger.repl(io::stdin().lock(), io::stdout(), true)?,
+    }
+    Ok(())
+}
+
+/// Writes the image the input draws on the CRT as `text` or `pbm` to `path` or stdout.
+pub fn day10_render(format: &str, path: Option<&Path>) -> anyhow::Result<()> {
+    let program = parse_program(include_str!("../../input/10.txt"))?;
+    let image = Crt::default().draw(&program);
+    let output = match format {
+        "text" => render_text(&image) + "\n",
+        "pbm" => render_pbm(&image),
+        _ => anyhow::bail!("Unknown format {format}, expected text or pbm"),
+    };
+    match path {
+        Some(path) => std::fs::write(path, output)?,
+        None => print!("{output}"),
+    }
+    Ok(())
 }
 
-fn day10a(input: &str) {
-    let sum: i32 = cycles(input)
-        .filter(|(cycle, _)| cycle % 40 == 20)
-        .map(|(cycle, x)| cycle * x)
-        .sum();
+/// Assembles the source in the file `source` into a plain program.
+pub fn day10_assemble(source: &Path) -> anyhow::Result<()> {
+    let assembly = assemble(&std::fs::read_to_string(source)?)?;
+    println!("{}", assembly.program.iter().join("\n"));
+    Ok(())
+}
+
+/// Prints the program in the file `program` with the cycles and X value of every instruction.
+pub fn day10_disassemble(program: &Path) -> anyhow::Result<()> {
+    let program = parse_program(&std::fs::read_to_string(program)?)?;
+    println!("{}", disassemble(&program));
+    Ok(())
+}
+
+/// Prints a program that draws `text` on the CRT, after checking it with the simulator.
+pub fn day10_synthesize_text(text: &str) -> anyhow::Result<()> {
+    let program = synthesize_text(&Crt::default(), text)?;
+    println!("{}", program.iter().join("\n"));
+    Ok(())
+}
+
+/// Prints a program that draws the image in the file `path`, with `#` for lit pixels, on the
+/// CRT, after checking it with the simulator.
+pub fn day10_synthesize_image(path: &Path) -> anyhow::Result<()> {
+    let crt = Crt::default();
+    let image = parse_image(&std::fs::read_to_string(path)?);
+    let program = synthesize(&crt, &image)?;
+    anyhow::ensure!(
+        crt.draw(&program) == image,
+        "The program draws a different image"
+    );
+    println!("{}", program.iter().join("\n"));
+    Ok(())
+}
+
+fn day10a(program: &[Instruction]) {
+    let sum = Crt::default().signal_strength(program);
     println!("Day10a: {sum}")
 }
 
-fn day10b(input: &str) {
-    let mut iter = iter::once((0, 0)).chain(cycles(input));
-    iter.next_back();
+fn day10b(program: &[Instruction]) {
+    let image = Crt::default().draw(program);
+    match read_letters(&image) {
+        Ok(letters) => println!("Day10b: {letters}"),
+        Err(e) => println!("Day10b: {e}\n{}", render_text(&image)),
+    }
+}
+
+/// The registers of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Registers {
+    x: i32,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers { x: 1 }
+    }
+}
+
+/// An instruction of the device. A new instruction needs its cycle cost, its effect on the
+/// registers, and a way to be parsed and printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Noop,
+    Addx(i32),
+}
+
+impl Instruction {
+    /// The number of cycles the instruction takes. Its effect is only visible after the last one.
+    fn cycles(self) -> usize {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+        }
+    }
+
+    fn execute(self, registers: &mut Registers) {
+        match self {
+            Instruction::Noop => {}
+            Instruction::Addx(value) => registers.x += value,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(value) => write!(f, "addx {value}"),
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ProgramError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let instruction = match (words.next(), words.next()) {
+            (Some("noop"), None) => Instruction::Noop,
+            (Some("addx"), Some(value)) => Instruction::Addx(
+                value
+                    .parse()
+                    .map_err(|_| ProgramError::InvalidArgument(s.to_string()))?,
+            ),
+            _ => return Err(ProgramError::UnknownInstruction(s.to_string())),
+        };
+        match words.next() {
+            Some(_) => Err(ProgramError::UnknownInstruction(s.to_string())),
+            None => Ok(instruction),
+        }
+    }
+}
 
-    let output = iter
-        .chunks(40)
-        .into_iter()
-        .map(|chunk| chunk.map(render_pixel).collect::<String>())
-        .join("\n");
-    println!("Day10b:\n{output}")
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum ProgramError {
+    #[error("Unknown instruction: {0}")]
+    UnknownInstruction(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Line {line}: {source}")]
+    Line {
+        line: usize,
+        #[source]
+        source: Box<ProgramError>,
+    },
 }
 
-fn cycles(input: &str) -> impl DoubleEndedIterator<Item = (i32, i32)> + '_ {
+/// Parses one instruction per line, reporting errors with their 1-based line number.
+fn parse_program(input: &str) -> Result<Vec<Instruction>, ProgramError> {
     input
         .lines()
-        .flat_map(parse_command)
-        .scan((1, 1), |acc, new| {
-            *acc = (acc.0 + 1, acc.1 + new);
-            Some(*acc)
+        .enumerate()
+        .map(|(index, line)| {
+            line.parse().map_err(|e| ProgramError::Line {
+                line: index + 1,
+                source: Box::new(e),
+            })
         })
-        .collect::<Vec<_>>()
-        .into_iter()
+        .collect()
 }
 
-fn parse_command(line: &str) -> Vec<i32> {
-    if line.starts_with("noop") {
-        vec![0]
-    } else if line.starts_with("addx ") {
-        let to_add: i32 = line.split_once(' ').unwrap().1.parse().unwrap();
-        vec![0, to_add]
-    } else {
-        panic!()
+/// The state of the device during a cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tick {
+    /// The 1-based number of the cycle
+    cycle: usize,
+    /// The registers during the cycle, before the instruction finishes
+    registers: Registers,
+    /// The index of the instruction being executed
+    pc: usize,
+    instruction: Instruction,
+}
+
+/// Gets called for every cycle the device runs.
+trait Observer {
+    fn observe(&mut self, tick: &Tick);
+}
+
+impl<F: FnMut(&Tick)> Observer for F {
+    fn observe(&mut self, tick: &Tick) {
+        self(tick)
+    }
+}
+
+/// Executes a program one cycle at a time.
+#[derive(Debug, Clone)]
+struct Cpu {
+    program: Vec<Instruction>,
+    registers: Registers,
+    pc: usize,
+    /// The number of cycles that have completed
+    cycle: usize,
+    /// The number of cycles the current instruction still needs
+    remaining: usize,
+}
+
+impl Cpu {
+    fn new(program: Vec<Instruction>) -> Self {
+        Cpu {
+            program,
+            registers: Registers::default(),
+            pc: 0,
+            cycle: 0,
+            remaining: 0,
+        }
+    }
+
+    fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Runs a single cycle and returns the state during it, or `None` once the program is done.
+    fn tick(&mut self) -> Option<Tick> {
+        let instruction = *self.program.get(self.pc)?;
+        if self.remaining == 0 {
+            self.remaining = instruction.cycles();
+        }
+        self.cycle += 1;
+        let tick = Tick {
+            cycle: self.cycle,
+            registers: self.registers,
+            pc: self.pc,
+            instruction,
+        };
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            instruction.execute(&mut self.registers);
+            self.pc += 1;
+        }
+        Some(tick)
+    }
+
+    /// Runs the program to the end, showing every cycle to `observer`.
+    fn run(&mut self, observer: &mut impl Observer) {
+        while let Some(tick) = self.tick() {
+            observer.observe(&tick);
+        }
+    }
+
+    fn ticks(&mut self) -> impl Iterator<Item = Tick> + '_ {
+        std::iter::from_fn(|| self.tick())
+    }
+}
+
+impl fmt::Display for Tick {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: X={} pc={} {}",
+            self.cycle, self.registers.x, self.pc, self.instruction
+        )
     }
 }
 
-fn render_pixel((cycle, x): (i32, i32)) -> char {
-    let difference = ((x) - ((cycle - 1) % 40)).abs();
-    if difference < 2 {
-        '█'
+const DEBUGGER_HELP: &str = "Commands:
+  step [n]          run n cycles (default 1) and show each of them
+  until <cycle>     run until the given cycle, a breakpoint or the end
+  run               run until a breakpoint or the end
+  break cycle <n>   stop during cycle n
+  break x <value>   stop when X becomes value
+  delete [n]        delete breakpoint n, or all of them
+  breakpoints       list the breakpoints
+  regs              show the registers
+  crt               show the CRT row being drawn
+  quit              stop debugging";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breakpoint {
+    Cycle(usize),
+    X(i32),
+}
+
+impl Breakpoint {
+    fn is_hit(self, tick: &Tick, previous: Option<&Tick>) -> bool {
+        match self {
+            Breakpoint::Cycle(cycle) => tick.cycle == cycle,
+            Breakpoint::X(x) => {
+                tick.registers.x == x && previous.is_none_or(|previous| previous.registers.x != x)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {cycle}"),
+            Breakpoint::X(x) => write!(f, "X = {x}"),
+        }
+    }
+}
+
+/// Runs a program under control of debugger commands, drawing the CRT along the way.
+struct Debugger {
+    cpu: Cpu,
+    crt: Crt,
+    breakpoints: Vec<Breakpoint>,
+    pixels: Vec<char>,
+    last: Option<Tick>,
+}
+
+impl Debugger {
+    fn new(program: Vec<Instruction>) -> Self {
+        Debugger {
+            cpu: Cpu::new(program),
+            crt: Crt::default(),
+            breakpoints: vec![],
+            pixels: vec![],
+            last: None,
+        }
+    }
+
+    /// Executes every line of `input` as a command and writes the results to `output`. Empty
+    /// lines and lines starting with `#` are skipped, so scripts can be commented.
+    fn repl(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+        prompt: bool,
+    ) -> io::Result<()> {
+        if prompt {
+            write!(output, "(debug) ")?;
+            output.flush()?;
+        }
+        for line in input.lines() {
+            let line = line?;
+            let command = line.trim();
+            if command == "quit" || command == "q" {
+                break;
+            }
+            if !command.is_empty() && !command.starts_with('#') {
+                writeln!(output, "{}", self.execute(command))?;
+            }
+            if prompt {
+                write!(output, "(debug) ")?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: &str) -> String {
+        let words = command.split_whitespace().collect_vec();
+        match words[..] {
+            ["step" | "s"] => self.step(1),
+            ["step" | "s", n] => match n.parse() {
+                Ok(n) => self.step(n),
+                Err(_) => format!("Not a number of cycles: {n}"),
+            },
+            ["until" | "u", cycle] => match cycle.parse() {
+                Ok(cycle) if cycle <= self.cpu.cycle => {
+                    format!("Already past cycle {cycle}")
+                }
+                Ok(cycle) => self.run_until(|tick| tick.cycle == cycle),
+                Err(_) => format!("Not a cycle: {cycle}"),
+            },
+            ["run" | "r"] => self.run_until(|_| false),
+            ["break" | "b", "cycle", cycle] => match cycle.parse() {
+                Ok(cycle) => self.add_breakpoint(Breakpoint::Cycle(cycle)),
+                Err(_) => format!("Not a cycle: {cycle}"),
+            },
+            ["break" | "b", "x" | "X", x] => match x.parse() {
+                Ok(x) => self.add_breakpoint(Breakpoint::X(x)),
+                Err(_) => format!("Not a value: {x}"),
+            },
+            ["delete" | "d"] => {
+                self.breakpoints.clear();
+                "Deleted all breakpoints".to_string()
+            }
+            ["delete" | "d", n] => match n.parse::<usize>() {
+                Ok(n) if (1..=self.breakpoints.len()).contains(&n) => {
+                    let breakpoint = self.breakpoints.remove(n - 1);
+                    format!("Deleted breakpoint {n} ({breakpoint})")
+                }
+                _ => format!("No breakpoint {n}"),
+            },
+            ["breakpoints"] if self.breakpoints.is_empty() => "No breakpoints".to_string(),
+            ["breakpoints"] => self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(index, breakpoint)| format!("{}: {breakpoint}", index + 1))
+                .join("\n"),
+            ["regs" | "registers"] => self.registers(),
+            ["crt"] => self.crt(),
+            ["help" | "h"] => DEBUGGER_HELP.to_string(),
+            _ => format!("Unknown command: {command}"),
+        }
+    }
+
+    fn advance(&mut self) -> Option<Tick> {
+        let tick = self.cpu.tick()?;
+        let lit = self.crt.is_lit(tick.cycle, tick.registers.x);
+        self.pixels.push(if lit { '█' } else { ' ' });
+        Some(tick)
+    }
+
+    fn step(&mut self, cycles: usize) -> String {
+        let mut lines = vec![];
+        for _ in 0..cycles {
+            match self.advance() {
+                Some(tick) => {
+                    lines.push(tick.to_string());
+                    self.last = Some(tick);
+                }
+                None => {
+                    lines.push(self.finished());
+                    break;
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Runs until `stop` is true for a cycle or a breakpoint is hit.
+    fn run_until(&mut self, stop: impl Fn(&Tick) -> bool) -> String {
+        loop {
+            let Some(tick) = self.advance() else {
+                return self.finished();
+            };
+            let previous = self.last.replace(tick);
+            let hit = self
+                .breakpoints
+                .iter()
+                .position(|breakpoint| breakpoint.is_hit(&tick, previous.as_ref()));
+            if let Some(index) = hit {
+                return format!(
+                    "Breakpoint {} ({}) hit\n{tick}",
+                    index + 1,
+                    self.breakpoints[index]
+                );
+            }
+            if stop(&tick) {
+                return tick.to_string();
+            }
+        }
+    }
+
+    fn finished(&self) -> String {
+        format!(
+            "Program finished after {} cycles with X={}",
+            self.cpu.cycle, self.cpu.registers.x
+        )
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> String {
+        self.breakpoints.push(breakpoint);
+        format!("Breakpoint {} at {breakpoint}", self.breakpoints.len())
+    }
+
+    /// The registers after the last cycle, which are the ones the next cycle starts with.
+    fn registers(&self) -> String {
+        let next = match self.cpu.program.get(self.cpu.pc) {
+            Some(instruction) => instruction.to_string(),
+            None => "halted".to_string(),
+        };
+        format!(
+            "X={} pc={} cycle={} next: {next}",
+            self.cpu.registers.x, self.cpu.pc, self.cpu.cycle
+        )
+    }
+
+    /// The row of the CRT that is being drawn, with the pixels that are not drawn yet as `.`, and
+    /// the position of the sprite for the next pixel below it. Once the frame is complete, this
+    /// stays at the last row.
+    fn crt(&self) -> String {
+        let width = self.crt.width;
+        let row = (self.pixels.len() / width).min(self.crt.height - 1);
+        let drawn = &self.pixels[row * width..self.pixels.len().min((row + 1) * width)];
+        let pixels = drawn
+            .iter()
+            .chain(std::iter::repeat_n(&'.', width - drawn.len()))
+            .collect::<String>();
+        let sprite = self.crt.sprite(self.cpu.registers.x);
+        let sprite = (0..width as i32)
+            .map(|column| if sprite.contains(&column) { '^' } else { ' ' })
+            .collect::<String>();
+        format!("Row {}: {pixels}\nSprite: {}", row + 1, sprite.trim_end())
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum AssemblyError {
+    #[error("Line {0}: expected {{ after repeat")]
+    MissingOpen(usize),
+    #[error("Line {0}: repeat without a matching }}")]
+    UnclosedRepeat(usize),
+    #[error("Line {0}: }} without a matching repeat")]
+    UnmatchedClose(usize),
+    #[error("Line {0}: {{ without a repeat")]
+    UnexpectedOpen(usize),
+    #[error("Line {line}: invalid repeat count {count}")]
+    InvalidCount { line: usize, count: String },
+    #[error("Line {line}: duplicate label {label}")]
+    DuplicateLabel { line: usize, label: String },
+    #[error("Line {line}: {source}")]
+    Instruction {
+        line: usize,
+        #[source]
+        source: ProgramError,
+    },
+}
+
+/// A piece of assembler source, with its 1-based line number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Text(usize, &'a str),
+    Open(usize),
+    Close(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Instruction(Instruction),
+    Label(String),
+    Repeat(usize, Vec<Statement>),
+}
+
+/// An assembled program, with the index of the instruction following every label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Assembly {
+    program: Vec<Instruction>,
+    labels: Vec<(String, usize)>,
+}
+
+/// Splits the source into statements and braces, dropping comments that start with `;`.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split(';').next().unwrap();
+        let mut rest = line;
+        while let Some(brace) = rest.find(['{', '}']) {
+            tokens.push(Token::Text(line_number, &rest[..brace]));
+            tokens.push(match &rest[brace..brace + 1] {
+                "{" => Token::Open(line_number),
+                _ => Token::Close(line_number),
+            });
+            rest = &rest[brace + 1..];
+        }
+        tokens.push(Token::Text(line_number, rest));
+    }
+    tokens.retain(|token| !matches!(token, Token::Text(_, text) if text.trim().is_empty()));
+    tokens
+}
+
+/// Parses statements until the end of the source, or until the `}` closing the block that
+/// starts at `open` if given. Every label is added to `labels`, which must not hold it yet.
+fn parse_block<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+    open: Option<usize>,
+    labels: &mut Vec<String>,
+) -> Result<Vec<Statement>, AssemblyError> {
+    let mut statements = vec![];
+    while let Some(token) = tokens.next() {
+        let (line, text) = match token {
+            Token::Close(line) => {
+                return match open {
+                    Some(_) => Ok(statements),
+                    None => Err(AssemblyError::UnmatchedClose(line)),
+                }
+            }
+            Token::Open(line) => return Err(AssemblyError::UnexpectedOpen(line)),
+            Token::Text(line, text) => (line, text.trim()),
+        };
+
+        let mut text = text;
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim().to_string();
+            if labels.contains(&label) {
+                return Err(AssemblyError::DuplicateLabel { line, label });
+            }
+            labels.push(label.clone());
+            statements.push(Statement::Label(label));
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(count) = text.strip_prefix("repeat ") {
+            let count = count.trim();
+            let count = count.parse().map_err(|_| AssemblyError::InvalidCount {
+                line,
+                count: count.to_string(),
+            })?;
+            if !matches!(tokens.next(), Some(Token::Open(_))) {
+                return Err(AssemblyError::MissingOpen(line));
+            }
+            statements.push(Statement::Repeat(
+                count,
+                parse_block(tokens, Some(line), labels)?,
+            ));
+        } else {
+            let instruction = text
+                .parse()
+                .map_err(|source| AssemblyError::Instruction { line, source })?;
+            statements.push(Statement::Instruction(instruction));
+        }
+    }
+    match open {
+        Some(line) => Err(AssemblyError::UnclosedRepeat(line)),
+        None => Ok(statements),
+    }
+}
+
+/// Appends the instructions of `statements` to the assembly. A label inside a `repeat` block
+/// refers to its first repetition.
+fn expand(statements: &[Statement], assembly: &mut Assembly) {
+    for statement in statements {
+        match statement {
+            Statement::Instruction(instruction) => assembly.program.push(*instruction),
+            Statement::Label(label) => {
+                if assembly.labels.iter().all(|(known, _)| known != label) {
+                    assembly
+                        .labels
+                        .push((label.clone(), assembly.program.len()));
+                }
+            }
+            Statement::Repeat(count, block) => {
+                for _ in 0..*count {
+                    expand(block, assembly);
+                }
+            }
+        }
+    }
+}
+
+/// Assembles source with one instruction per line, `label:` markers, comments starting with `;`
+/// and `repeat n { ... }` blocks, which may be nested and written on a single line.
+fn assemble(source: &str) -> Result<Assembly, AssemblyError> {
+    let mut tokens = tokenize(source).into_iter();
+    let statements = parse_block(&mut tokens, None, &mut vec![])?;
+    let mut assembly = Assembly {
+        program: vec![],
+        labels: vec![],
+    };
+    expand(&statements, &mut assembly);
+    Ok(assembly)
+}
+
+/// Prints the program with the cycles every instruction runs during and the value of X during
+/// them as comments, so the output can be assembled again.
+fn disassemble(program: &[Instruction]) -> String {
+    let ticks = Cpu::new(program.to_vec()).ticks().collect_vec();
+    ticks
+        .chunk_by(|a, b| a.pc == b.pc)
+        .map(|ticks| {
+            let (first, last) = (ticks[0], ticks[ticks.len() - 1]);
+            let cycles = match first.cycle == last.cycle {
+                true => format!("cycle {}", first.cycle),
+                false => format!("cycles {}-{}", first.cycle, last.cycle),
+            };
+            let instruction = first.instruction.to_string();
+            format!("{instruction:<10}; {cycles}, X={}", first.registers.x)
+        })
+        .join("\n")
+}
+
+/// The letters the CRT can show, each 4 pixels wide and 6 pixels high.
+const FONT: [(char, [&str; 6]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+/// The distance between the left edges of neighbouring glyphs
+const GLYPH_SPACING: usize = 5;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum OcrError {
+    #[error("Expected an image {GLYPH_HEIGHT} pixels high, got {0}")]
+    WrongHeight(usize),
+    #[error("Unrecognised glyphs at positions {positions:?}: {text}")]
+    Unrecognised {
+        /// The recognised text with `?` for every unrecognised glyph
+        text: String,
+        positions: Vec<usize>,
+    },
+}
+
+/// The pixels of a glyph as bits, row by row.
+fn glyph_bits(pixel: impl Fn(usize, usize) -> bool) -> u32 {
+    (0..GLYPH_HEIGHT)
+        .flat_map(|row| (0..GLYPH_WIDTH).map(move |column| (row, column)))
+        .fold(0, |bits, (row, column)| {
+            bits << 1 | pixel(row, column) as u32
+        })
+}
+
+/// Reads the letters shown on an image of the CRT. Empty glyphs are read as spaces.
+fn read_letters(image: &[Vec<bool>]) -> Result<String, OcrError> {
+    if image.len() != GLYPH_HEIGHT {
+        return Err(OcrError::WrongHeight(image.len()));
+    }
+    let width = image.iter().map(Vec::len).max().unwrap_or(0);
+    let font = FONT
+        .iter()
+        .map(|(letter, rows)| {
+            let bits = glyph_bits(|row, column| rows[row].as_bytes()[column] == b'#');
+            (bits, *letter)
+        })
+        .collect_vec();
+
+    let mut text = String::new();
+    let mut positions = vec![];
+    for (position, left) in (0..width).step_by(GLYPH_SPACING).enumerate() {
+        let bits =
+            glyph_bits(|row, column| image[row].get(left + column).copied().unwrap_or(false));
+        match font.iter().find(|(glyph, _)| *glyph == bits) {
+            Some((_, letter)) => text.push(*letter),
+            None if bits == 0 => text.push(' '),
+            None => {
+                text.push('?');
+                positions.push(position);
+            }
+        }
+    }
+
+    if positions.is_empty() {
+        Ok(text)
     } else {
-        ' '
+        Err(OcrError::Unrecognised { text, positions })
+    }
+}
+
+/// The cycles during which the signal strength is measured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Schedule {
+    /// Every `period` cycles, starting with cycle `first`
+    Every {
+        first: usize,
+        period: usize,
+    },
+    Cycles(Vec<usize>),
+}
+
+impl Schedule {
+    fn contains(&self, cycle: usize) -> bool {
+        match self {
+            Schedule::Every { first, period } => {
+                cycle >= *first && (cycle - first).is_multiple_of(*period)
+            }
+            Schedule::Cycles(cycles) => cycles.contains(&cycle),
+        }
+    }
+}
+
+/// The screen of the device, which draws one pixel per cycle, row by row. A pixel is lit when
+/// it is covered by the sprite, which is centered on X.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Crt {
+    width: usize,
+    height: usize,
+    sprite_width: usize,
+    schedule: Schedule,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Crt::new(40, 6, 3)
+    }
+}
+
+impl Crt {
+    /// Panics if `width` or `height` is zero, as there would be no pixels to draw.
+    fn new(width: usize, height: usize, sprite_width: usize) -> Self {
+        assert!(width > 0 && height > 0, "CRT of {width}x{height} pixels");
+        Crt {
+            width,
+            height,
+            sprite_width,
+            schedule: Schedule::Every {
+                first: 20,
+                period: 40,
+            },
+        }
+    }
+
+    fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// The columns covered by the sprite when X is `x`. Sprites of even width reach further to
+    /// the right.
+    fn sprite(&self, x: i32) -> RangeInclusive<i32> {
+        let start = x - (self.sprite_width as i32 - 1) / 2;
+        start..=start + self.sprite_width as i32 - 1
+    }
+
+    /// Whether the pixel drawn during `cycle` is lit when X is `x`.
+    fn is_lit(&self, cycle: usize, x: i32) -> bool {
+        let column = (cycle - 1) % self.width;
+        self.sprite(x).contains(&(column as i32))
+    }
+
+    /// The sum of the signal strengths during the scheduled cycles.
+    fn signal_strength(&self, program: &[Instruction]) -> i32 {
+        let mut sum = 0;
+        Cpu::new(program.to_vec()).run(&mut |tick: &Tick| {
+            if self.schedule.contains(tick.cycle) {
+                sum += tick.cycle as i32 * tick.registers.x;
+            }
+        });
+        sum
+    }
+
+    /// The image the program draws, row by row. Pixels the program doesn't run long enough for
+    /// stay dark, and cycles after the last pixel are not drawn.
+    fn draw(&self, program: &[Instruction]) -> Vec<Vec<bool>> {
+        let mut image = vec![vec![false; self.width]; self.height];
+        let pixels = self.width * self.height;
+        for tick in Cpu::new(program.to_vec()).ticks().take(pixels) {
+            let index = tick.cycle - 1;
+            image[index / self.width][index % self.width] =
+                self.is_lit(tick.cycle, tick.registers.x);
+        }
+        image
     }
 }
 
+fn render_text(image: &[Vec<bool>]) -> String {
+    image
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|lit| if *lit { '█' } else { ' ' })
+                .collect::<String>()
+        })
+        .join("\n")
+}
+
+/// A plain PBM image with lit pixels in black.
+fn render_pbm(image: &[Vec<bool>]) -> String {
+    let width = image.first().map_or(0, Vec::len);
+    let mut out = format!("P1\n{width} {}\n", image.len());
+    for row in image {
+        out.push_str(&row.iter().map(|lit| if *lit { "1" } else { "0" }).join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// Reads an image with `#` for lit pixels and anything else for dark ones.
+fn parse_image(text: &str) -> Vec<Vec<bool>> {
+    text.lines()
+        .map(|row| row.chars().map(|pixel| pixel == '#').collect())
+        .collect()
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+enum SynthesisError {
+    #[error("Expected a {}x{} image, got {}x{}", .expected.0, .expected.1, .actual.0, .actual.1)]
+    WrongSize {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    #[error("No glyph for {0:?}")]
+    UnknownLetter(char),
+    #[error("{0} letters don't fit on the screen")]
+    TooLong(usize),
+    #[error("No program can draw the pixel in row {row}, column {column}")]
+    Unreachable { row: usize, column: usize },
+}
+
+/// The image of `text` written in the CRT font in the top left corner of the screen.
+fn text_image(crt: &Crt, text: &str) -> Result<Vec<Vec<bool>>, SynthesisError> {
+    let length = text.chars().count();
+    if length * GLYPH_SPACING > crt.width + GLYPH_SPACING - GLYPH_WIDTH || crt.height < GLYPH_HEIGHT
+    {
+        return Err(SynthesisError::TooLong(length));
+    }
+    let mut image = vec![vec![false; crt.width]; crt.height];
+    for (position, letter) in text.chars().enumerate() {
+        if letter == ' ' {
+            continue;
+        }
+        let (_, rows) = FONT
+            .iter()
+            .find(|(glyph, _)| *glyph == letter)
+            .ok_or(SynthesisError::UnknownLetter(letter))?;
+        for (row, pixels) in rows.iter().enumerate() {
+            for (column, pixel) in pixels.bytes().enumerate() {
+                image[row][position * GLYPH_SPACING + column] = pixel == b'#';
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Finds a program that draws `text` on `crt` and checks that the letters can be read back. The
+/// glyphs after the text are read as spaces, so trailing spaces are ignored.
+fn synthesize_text(crt: &Crt, text: &str) -> anyhow::Result<Vec<Instruction>> {
+    let image = text_image(crt, text)?;
+    let program = synthesize(crt, &image)?;
+    let letters = read_letters(&crt.draw(&program))?;
+    anyhow::ensure!(
+        letters.trim_end() == text.trim_end(),
+        "The program draws {letters} instead"
+    );
+    Ok(program)
+}
+
+/// Finds a program that draws exactly `image` on `crt` within one frame. Starting with X at 1, it
+/// tracks every value of X the program can have at the start of every cycle without drawing a
+/// wrong pixel, and how it got there. Values of X too far off the screen to matter are not tried.
+/// As X can't change before the third cycle, the first two pixels have to match a sprite at 1.
+fn synthesize(crt: &Crt, image: &[Vec<bool>]) -> Result<Vec<Instruction>, SynthesisError> {
+    let actual = (image.first().map_or(0, Vec::len), image.len());
+    if actual != (crt.width, crt.height) || image.iter().any(|row| row.len() != crt.width) {
+        return Err(SynthesisError::WrongSize {
+            expected: (crt.width, crt.height),
+            actual,
+        });
+    }
+    let target = image.concat();
+    let pixels = target.len();
+    let margin = crt.sprite_width as i32 + 1;
+    let xs = -margin..=crt.width as i32 + margin;
+    let index = |x: i32| (x + margin) as usize;
+    let allowed = |cycle: usize, x: i32| crt.is_lit(cycle + 1, x) == target[cycle];
+
+    // For every cycle and value of X the value of X before the last instruction and the
+    // instruction itself
+    let mut from: Vec<Vec<Option<(i32, Instruction)>>> =
+        vec![vec![None; xs.clone().count()]; pixels + 1];
+    from[0][index(1)] = Some((1, Instruction::Noop));
+    let mut last = 0;
+    for cycle in 0..pixels {
+        for x in xs.clone() {
+            if from[cycle][index(x)].is_none() || !allowed(cycle, x) {
+                continue;
+            }
+            last = cycle + 1;
+            from[cycle + 1][index(x)].get_or_insert((x, Instruction::Noop));
+            if cycle + 2 <= pixels && allowed(cycle + 1, x) {
+                last = cycle + 2;
+                for next in xs.clone().filter(|next| *next != x) {
+                    from[cycle + 2][index(next)].get_or_insert((x, Instruction::Addx(next - x)));
+                }
+            }
+        }
+    }
+
+    // X after the last cycle doesn't matter, so the program ends with the smallest change
+    let end = xs
+        .clone()
+        .filter_map(|x| Some((x, from[pixels][index(x)]?)));
+    let end = end.min_by_key(|(_, (_, instruction))| match instruction {
+        Instruction::Noop => 0,
+        Instruction::Addx(value) => value.abs(),
+    });
+    let Some((mut x, _)) = end else {
+        return Err(SynthesisError::Unreachable {
+            row: last / crt.width,
+            column: last % crt.width,
+        });
+    };
+    let mut program = vec![];
+    let mut cycle = pixels;
+    while cycle > 0 {
+        let (previous, instruction) = from[cycle][index(x)].unwrap();
+        program.push(instruction);
+        cycle -= instruction.cycles();
+        x = previous;
+    }
+    program.reverse();
+    Ok(program)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn test_render_pixel() {
-        assert_eq!('█', render_pixel((1, 1)));
-        assert_eq!('█', render_pixel((2, 1)));
-        assert_eq!('█', render_pixel((3, 1)));
-        assert_eq!(' ', render_pixel((4, 1)));
+        let crt = Crt::default();
+        assert!(crt.is_lit(1, 1));
+        assert!(crt.is_lit(2, 1));
+        assert!(crt.is_lit(3, 1));
+        assert!(!crt.is_lit(4, 1));
+    }
+
+    #[test]
+    fn test_crt() {
+        let program = parse_program(include_str!("../../input/10.txt")).unwrap();
+        assert_eq!(Crt::default().signal_strength(&program), 12560);
+        let schedule = Schedule::Cycles(vec![1, 20]);
+        let crt = Crt::default().with_schedule(schedule);
+        assert_eq!(crt.signal_strength(&program), 1 + 20 * 21);
+
+        let program = parse_program("addx 2\nnoop\naddx -3\naddx 5").unwrap();
+        let crt = Crt::new(4, 2, 1);
+        let image = crt.draw(&program);
+        assert_eq!(
+            image,
+            vec![
+                vec![false, true, false, true],
+                vec![false, false, false, false]
+            ]
+        );
+        assert_eq!(render_text(&image), " █ █\n    ");
+        assert_eq!(render_pbm(&image), "P1\n4 2\n0 1 0 1\n0 0 0 0\n");
+
+        let crt = Crt::new(3, 1, 4);
+        assert_eq!(crt.sprite(1), 0..=3);
+        assert_eq!(crt.draw(&program), vec![vec![true, true, true]]);
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let program = parse_program("noop\naddx 3\naddx -5").unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::Noop,
+                Instruction::Addx(3),
+                Instruction::Addx(-5)
+            ]
+        );
+        assert_eq!(program.iter().join("\n"), "noop\naddx 3\naddx -5");
+
+        let error = parse_program("noop\njmp 3").unwrap_err();
+        assert_eq!(error.to_string(), "Line 2: Unknown instruction: jmp 3");
+        let error = parse_program("addx three").unwrap_err();
+        assert_eq!(error.to_string(), "Line 1: Invalid argument: addx three");
+        assert!(parse_program("noop 1").is_err());
+        assert!(parse_program("addx").is_err());
+    }
+
+    #[test]
+    fn test_cpu() {
+        let program = parse_program("noop\naddx 3\naddx -5").unwrap();
+        let mut cpu = Cpu::new(program);
+        let ticks = cpu
+            .ticks()
+            .map(|tick| (tick.cycle, tick.registers.x, tick.pc))
+            .collect_vec();
+        assert_eq!(
+            ticks,
+            vec![(1, 1, 0), (2, 1, 1), (3, 1, 1), (4, 4, 2), (5, 4, 2)]
+        );
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.x, -1);
+        assert_eq!(cpu.tick(), None);
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; draws a bar
+            start:
+            repeat 2 { noop }
+            addx 3 ; move right
+            repeat 2 {
+                repeat 3 { noop } addx -1
+            }
+            end: noop
+            repeat 3 { loop: noop }";
+        let assembly = assemble(source).unwrap();
+        assert_eq!(
+            assembly.program.iter().join(","),
+            "noop,noop,addx 3,noop,noop,noop,addx -1,noop,noop,noop,addx -1,noop,noop,noop,noop"
+        );
+        assert_eq!(
+            assembly.labels,
+            vec![
+                ("start".to_string(), 0),
+                ("end".to_string(), 11),
+                ("loop".to_string(), 12)
+            ]
+        );
+
+        for (source, error) in [
+            ("noop\nrepeat 2\nnoop", AssemblyError::MissingOpen(2)),
+            ("repeat 2 {\nnoop", AssemblyError::UnclosedRepeat(1)),
+            ("noop }", AssemblyError::UnmatchedClose(1)),
+            ("{ noop }", AssemblyError::UnexpectedOpen(1)),
+            (
+                "repeat x { noop }",
+                AssemblyError::InvalidCount {
+                    line: 1,
+                    count: "x".to_string(),
+                },
+            ),
+            (
+                "a: noop\nrepeat 2 { a: noop }",
+                AssemblyError::DuplicateLabel {
+                    line: 2,
+                    label: "a".to_string(),
+                },
+            ),
+            (
+                "noop\n\nadd 1",
+                AssemblyError::Instruction {
+                    line: 3,
+                    source: ProgramError::UnknownInstruction("add 1".to_string()),
+                },
+            ),
+        ] {
+            assert_eq!(assemble(source), Err(error));
+        }
+        assert_eq!(
+            assemble("repeat 1 {\n}\n}").unwrap_err().to_string(),
+            "Line 3: } without a matching repeat"
+        );
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = parse_program("noop\naddx 3\naddx -5").unwrap();
+        assert_eq!(
+            disassemble(&program),
+            "noop      ; cycle 1, X=1\naddx 3    ; cycles 2-3, X=1\naddx -5   ; cycles 4-5, X=4"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = include_str!("../../input/10.txt");
+        let program = parse_program(input).unwrap();
+        let assembly = assemble(input).unwrap();
+        assert_eq!(assembly.program, program);
+        assert_eq!(assembly.program.iter().join("\n"), input.trim_end());
+
+        let disassembly = disassemble(&program);
+        assert_eq!(disassembly.lines().count(), program.len());
+        assert_eq!(assemble(&disassembly).unwrap().program, program);
+    }
+
+    fn image(rows: &[&str]) -> Vec<Vec<bool>> {
+        parse_image(&rows.join("\n"))
+    }
+
+    #[test]
+    fn test_synthesize_text() {
+        let crt = Crt::default();
+        for text in ["PLPAFBCL", "BACEFGHI", "EJKLOPRS", "ZU  Z"] {
+            let image = text_image(&crt, text).unwrap();
+            let program = synthesize(&crt, &image).unwrap();
+            assert_eq!(crt.draw(&program), image);
+            assert_eq!(read_letters(&crt.draw(&program)).unwrap().trim_end(), text);
+            let cycles: usize = program.iter().map(|instruction| instruction.cycles()).sum();
+            assert_eq!(cycles, 240);
+        }
+
+        for text in ["PLP", "P L", "E ", "ZU  Z"] {
+            let program = synthesize_text(&crt, text).unwrap();
+            assert_eq!(read_letters(&crt.draw(&program)).unwrap().len(), 8);
+        }
+        assert!(synthesize_text(&crt, "HELLO").is_err());
+
+        // The H would need the second pixel to be dark while the sprite is still on it
+        let image = text_image(&crt, "HELLO").unwrap();
+        assert_eq!(
+            synthesize(&crt, &image),
+            Err(SynthesisError::Unreachable { row: 0, column: 1 })
+        );
+        assert_eq!(
+            text_image(&crt, "HELLO!"),
+            Err(SynthesisError::UnknownLetter('!'))
+        );
+        assert_eq!(
+            text_image(&crt, "TOOLONGTEXT"),
+            Err(SynthesisError::TooLong(11))
+        );
+    }
+
+    #[test]
+    fn test_synthesize_image() {
+        let program = parse_program(include_str!("../../input/10.txt")).unwrap();
+        let crt = Crt::default();
+        let image = crt.draw(&program);
+        let synthesized = synthesize(&crt, &image).unwrap();
+        assert_eq!(crt.draw(&synthesized), image);
+
+        let crt = Crt::new(6, 2, 1);
+        let image = parse_image(".#..#.\n#....#");
+        let program = synthesize(&crt, &image).unwrap();
+        assert_eq!(crt.draw(&program), image);
+
+        let crt = Crt::default();
+        let mut image = vec![vec![false; 40]; 6];
+        image[0][0] = true;
+        assert_eq!(
+            synthesize(&crt, &image),
+            Err(SynthesisError::Unreachable { row: 0, column: 1 })
+        );
+        assert_eq!(
+            synthesize(&crt, &image[1..]).unwrap_err().to_string(),
+            "Expected a 40x6 image, got 40x5"
+        );
+    }
+
+    #[test]
+    fn test_read_letters() {
+        for (letter, rows) in FONT {
+            assert_eq!(read_letters(&image(&rows)), Ok(letter.to_string()));
+        }
+
+        let hello = image(&[
+            "#..#.####.#....#.....##.",
+            "#..#.#....#....#....#..#",
+            "####.###..#....#....#..#",
+            "#..#.#....#....#....#..#",
+            "#..#.#....#....#....#..#",
+            "#..#.####.####.####..##.",
+        ]);
+        assert_eq!(read_letters(&hello), Ok("HELLO".to_string()));
+
+        let unknown = image(&[
+            "#..#......#..#.####.####",
+            "#..#......#..#.#.......#",
+            "####......#..#.#......#.",
+            "#..#......####.#.....#..",
+            "#..#......##.#.#....#...",
+            "#..#......#..#.####.####",
+        ]);
+        assert_eq!(
+            read_letters(&unknown),
+            Err(OcrError::Unrecognised {
+                text: "H ??Z".to_string(),
+                positions: vec![2, 3]
+            })
+        );
+        assert_eq!(
+            read_letters(&unknown).unwrap_err().to_string(),
+            "Unrecognised glyphs at positions [2, 3]: H ??Z"
+        );
+        assert_eq!(read_letters(&hello[1..]), Err(OcrError::WrongHeight(5)));
+
+        let program = parse_program(include_str!("../../input/10.txt")).unwrap();
+        let image = Crt::default().draw(&program);
+        assert_eq!(read_letters(&image), Ok("PLPAFBCL".to_string()));
+    }
+
+    fn debug(program: &str, script: &str) -> String {
+        let mut debugger = Debugger::new(parse_program(program).unwrap());
+        let mut output = vec![];
+        debugger
+            .repl(script.as_bytes(), &mut output, false)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_debugger_step() {
+        let script = "step\nregs\nstep 2\nregs\n\n# done\nstep 5\nstep";
+        assert_eq!(
+            debug("noop\naddx 3\naddx -5", script),
+            "cycle 1: X=1 pc=0 noop
+X=1 pc=1 cycle=1 next: addx 3
+cycle 2: X=1 pc=1 addx 3
+cycle 3: X=1 pc=1 addx 3
+X=4 pc=2 cycle=3 next: addx -5
+cycle 4: X=4 pc=2 addx -5
+cycle 5: X=4 pc=2 addx -5
+Program finished after 5 cycles with X=-1
+Program finished after 5 cycles with X=-1
+"
+        );
+    }
+
+    #[test]
+    fn test_debugger_breakpoints() {
+        let program = include_str!("../../input/10.txt");
+        let script = "break cycle 20\nb x 5\nbreakpoints\nrun\nrun\ndelete 2\nuntil 150\n\
+            until 50\ndelete\nbreakpoints\nrun\nq\nregs";
+        let output = debug(program, script);
+        assert_eq!(
+            output.lines().collect_vec(),
+            vec![
+                "Breakpoint 1 at cycle 20",
+                "Breakpoint 2 at X = 5",
+                "1: cycle 20",
+                "2: X = 5",
+                "Breakpoint 1 (cycle 20) hit",
+                "cycle 20: X=21 pc=14 addx 1",
+                "Breakpoint 2 (X = 5) hit",
+                "cycle 117: X=5 pc=69 noop",
+                "Deleted breakpoint 2 (X = 5)",
+                "cycle 150: X=31 pc=90 addx 5",
+                "Already past cycle 50",
+                "Deleted all breakpoints",
+                "No breakpoints",
+                "Program finished after 240 cycles with X=37",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debugger_crt() {
+        let program = include_str!("../../input/10.txt");
+        let output = debug(program, "crt\nuntil 44\ncrt\nfoo");
+        assert_eq!(
+            output.lines().collect_vec(),
+            vec![
+                "Row 1: ........................................",
+                "Sprite: ^^^",
+                "cycle 44: X=2 pc=30 addx 2",
+                "Row 2: █  █....................................",
+                "Sprite:  ^^^",
+                "Unknown command: foo",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debugger_crt_complete() {
+        let program = "noop\n".repeat(250);
+        let output = debug(&program, "run\ncrt");
+        let lines = output.lines().collect_vec();
+        assert_eq!(
+            lines[lines.len() - 2],
+            format!("Row 6: ███{}", " ".repeat(37))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_crt_without_pixels() {
+        Crt::new(0, 6, 3);
     }
 }
@@ -3,12 +3,12 @@
 use itertools::Itertools;
 use miette::GraphicalReportHandler;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_while};
-use nom::character::complete::newline;
-use nom::combinator::map;
+use nom::bytes::complete::tag;
+use nom::character::complete::{newline, space0};
+use nom::combinator::{map, verify};
 use nom::error::ParseError;
-use nom::multi::{separated_list0, separated_list1};
-use nom::sequence::{separated_pair, terminated};
+use nom::multi::{many0, separated_list0, separated_list1};
+use nom::sequence::{delimited, pair, separated_pair, terminated};
 use nom::{IResult, Offset, Parser};
 use nom_locate::LocatedSpan;
 use nom_supreme::error::{BaseErrorKind, ErrorTree, GenericErrorTree};
@@ -16,7 +16,7 @@ use nom_supreme::final_parser::final_parser;
 use nom_supreme::parser_ext::ParserExt;
 use num::integer::lcm;
 use std::collections::VecDeque;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
 
 type Span<'a> = LocatedSpan<&'a str>;
 
@@ -131,42 +131,48 @@ fn business(monkeys: &[Monkey]) -> usize {
         .product()
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Monkey {
+    id: usize,
     items: VecDeque<usize>,
     activity: usize,
-    operation: Box<dyn Fn(usize) -> usize>,
+    operation: Expr,
     test: usize,
     actions: (usize, usize),
 }
 
-impl Debug for Monkey {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!(
-            "Monkey {{ items: {:?}, activity: {}, test: {}, actions: {:?} }}",
-            self.items, self.activity, self.test, self.actions
-        ))
+/// Writes the monkey in the input format, without its activity.
+impl Display for Monkey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Monkey {}:", self.id)?;
+        writeln!(f, "  Starting items: {}", self.items.iter().join(", "))?;
+        writeln!(f, "  Operation: new = {}", self.operation)?;
+        writeln!(f, "  Test: divisible by {}", self.test)?;
+        writeln!(f, "    If true: throw to monkey {}", self.actions.0)?;
+        write!(f, "    If false: throw to monkey {}", self.actions.1)
     }
 }
 
 fn parse_monkeys<'a, E: ParseError<Span<'a>> + 'static>(
     input: Span<'a>,
-) -> IResult<Span, Vec<Monkey>, E> {
+) -> IResult<Span<'a>, Vec<Monkey>, E> {
     separated_list1(newline, parse_monkey)(input)
 }
 
 fn parse_monkey<'a, E: ParseError<Span<'a>> + 'static>(
     input: Span<'a>,
 ) -> IResult<Span<'a>, Monkey, E> {
-    let (input, _) = terminated(take_while(|c: char| c != '\n'), newline)(input)?;
+    let (input, id) = delimited(tag("Monkey "), parse_usize, pair(tag(":"), newline))(input)?;
     let (input, items) = terminated(parse_items, newline)(input)?;
     let (input, operation) = terminated(parse_operation, newline)(input)?;
     let (input, test) = terminated(parse_test, newline)(input)?;
     let (input, actions) = terminated(parse_actions, newline)(input)?;
 
     let monkey = Monkey {
+        id,
         items: items.into(),
         activity: 0,
-        operation: Box::new(operation),
+        operation,
         test,
         actions,
     };
@@ -182,13 +188,13 @@ fn parse_items<'a, E: ParseError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>
     Ok((input, list))
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Operand {
     Old,
     Num(usize),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Symbol {
     Plus,
     Minus,
@@ -196,35 +202,149 @@ enum Symbol {
     Div,
 }
 
-fn parse_operation<'a, E: ParseError<Span<'a>>>(
-    input: Span<'a>,
-) -> IResult<Span<'a>, impl Fn(usize) -> usize, E> {
-    let (input, _) = tag("  Operation: new = ")(input)?;
-    let (input, first) = parse_operand(input)?;
-    let (input, _) = tag(" ")(input)?;
-    let (input, operation) = parse_symbol(input)?;
-    let (input, _) = tag(" ")(input)?;
-    let (input, second) = parse_operand(input)?;
-
-    let op = move |x| {
-        let operation = match operation {
-            Symbol::Plus => |a, b| a + b,
-            Symbol::Minus => |a, b| a - b,
-            Symbol::Mul => |a, b| a * b,
-            Symbol::Div => |a, b| a / b,
-        };
-        let fst = match first {
-            Operand::Old => x,
-            Operand::Num(y) => y,
+impl Symbol {
+    /// Symbols with a higher precedence bind more tightly.
+    fn precedence(self) -> u8 {
+        match self {
+            Symbol::Plus | Symbol::Minus => 1,
+            Symbol::Mul | Symbol::Div => 2,
+        }
+    }
+}
+
+/// The operation of a monkey, computing the new worry level from the old one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Expr {
+    Operand(Operand),
+    Binary(Box<Expr>, Symbol, Box<Expr>),
+}
+
+impl Expr {
+    fn binary(left: Expr, symbol: Symbol, right: Expr) -> Self {
+        Expr::Binary(Box::new(left), symbol, Box::new(right))
+    }
+
+    fn eval(&self, old: usize) -> usize {
+        match self {
+            Expr::Operand(Operand::Old) => old,
+            Expr::Operand(Operand::Num(num)) => *num,
+            Expr::Binary(left, symbol, right) => {
+                let (left, right) = (left.eval(old), right.eval(old));
+                match symbol {
+                    Symbol::Plus => left + right,
+                    Symbol::Minus => left - right,
+                    Symbol::Mul => left * right,
+                    Symbol::Div => left / right,
+                }
+            }
+        }
+    }
+
+    /// Evaluates the expression modulo `modulus`, without letting intermediate values grow past
+    /// it. Divisions don't work modulo a number, so expressions with one give `None`.
+    fn eval_mod(&self, old: usize, modulus: usize) -> Option<usize> {
+        let value = match self {
+            Expr::Operand(Operand::Old) => old % modulus,
+            Expr::Operand(Operand::Num(num)) => num % modulus,
+            Expr::Binary(left, symbol, right) => {
+                let left = left.eval_mod(old, modulus)? as u128;
+                let right = right.eval_mod(old, modulus)? as u128;
+                let modulus = modulus as u128;
+                let value = match symbol {
+                    Symbol::Plus => (left + right) % modulus,
+                    Symbol::Minus => (left + modulus - right) % modulus,
+                    Symbol::Mul => left * right % modulus,
+                    Symbol::Div => return None,
+                };
+                value as usize
+            }
         };
-        let snd = match second {
-            Operand::Old => x,
-            Operand::Num(y) => y,
+        Some(value)
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Operand(_) => u8::MAX,
+            Expr::Binary(_, symbol, _) => symbol.precedence(),
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Old => write!(f, "old"),
+            Operand::Num(num) => write!(f, "{num}"),
+        }
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Symbol::Plus => "+",
+            Symbol::Minus => "-",
+            Symbol::Mul => "*",
+            Symbol::Div => "/",
         };
+        f.write_str(symbol)
+    }
+}
 
-        operation(fst, snd)
-    };
-    Ok((input, op))
+/// Writes the expression with as few parentheses as parsing it back into the same tree allows.
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Operand(operand) => write!(f, "{operand}"),
+            Expr::Binary(left, symbol, right) => {
+                if left.precedence() < symbol.precedence() {
+                    write!(f, "({left})")?;
+                } else {
+                    write!(f, "{left}")?;
+                }
+                write!(f, " {symbol} ")?;
+                if right.precedence() <= symbol.precedence() {
+                    write!(f, "({right})")
+                } else {
+                    write!(f, "{right}")
+                }
+            }
+        }
+    }
+}
+
+fn parse_operation<'a, E: ParseError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>, Expr, E> {
+    let (input, _) = tag("  Operation: new = ")(input)?;
+    parse_expr(input)
+}
+
+/// Parses terms joined by `+` and `-`, which are left associative.
+fn parse_expr<'a, E: ParseError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>, Expr, E> {
+    let (input, first) = parse_term(input)?;
+    let symbol = verify(parse_symbol, |symbol| symbol.precedence() == 1);
+    let (input, rest) = many0(pair(delimited(space0, symbol, space0), parse_term))(input)?;
+    let expr = rest.into_iter().fold(first, |left, (symbol, right)| {
+        Expr::binary(left, symbol, right)
+    });
+    Ok((input, expr))
+}
+
+/// Parses factors joined by `*` and `/`, which are left associative.
+fn parse_term<'a, E: ParseError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>, Expr, E> {
+    let (input, first) = parse_factor(input)?;
+    let symbol = verify(parse_symbol, |symbol| symbol.precedence() == 2);
+    let (input, rest) = many0(pair(delimited(space0, symbol, space0), parse_factor))(input)?;
+    let expr = rest.into_iter().fold(first, |left, (symbol, right)| {
+        Expr::binary(left, symbol, right)
+    });
+    Ok((input, expr))
+}
+
+fn parse_factor<'a, E: ParseError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>, Expr, E> {
+    alt((
+        map(parse_operand, Expr::Operand),
+        delimited(pair(tag("("), space0), parse_expr, pair(space0, tag(")"))),
+    ))(input)
 }
 
 fn parse_operand<'a, E: ParseError<Span<'a>>>(input: Span<'a>) -> IResult<Span<'a>, Operand, E> {
@@ -270,10 +390,10 @@ fn simulate_round_a(monkeys: &mut [Monkey]) {
         {
             let monkey = &mut monkeys[i];
             while let Some(item) = monkey.items.pop_front() {
-                let mut worry = (monkey.operation)(item);
+                let mut worry = monkey.operation.eval(item);
                 monkey.activity += 1;
                 worry /= 3;
-                let index = if worry % monkey.test == 0 {
+                let index = if worry.is_multiple_of(monkey.test) {
                     monkey.actions.0
                 } else {
                     monkey.actions.1
@@ -296,10 +416,12 @@ fn simulate_round_b(monkeys: &mut [Monkey], lcm: usize) {
         {
             let monkey = &mut monkeys[i];
             while let Some(item) = monkey.items.pop_front() {
-                let mut worry = (monkey.operation)(item);
+                let worry = match monkey.operation.eval_mod(item, lcm) {
+                    Some(worry) => worry,
+                    None => monkey.operation.eval(item) % lcm,
+                };
                 monkey.activity += 1;
-                worry %= lcm;
-                let index = if worry % monkey.test == 0 {
+                let index = if worry.is_multiple_of(monkey.test) {
                     monkey.actions.0
                 } else {
                     monkey.actions.1
@@ -391,6 +513,57 @@ mod test {
         let input = "  Operation: new = old * 19";
         let actual = parse_operation::<ErrType>(Span::new(input));
         assert!(actual.is_ok(), "{}", actual.err().unwrap());
+        assert_eq!(
+            actual.unwrap().1,
+            Expr::binary(
+                Expr::Operand(Operand::Old),
+                Symbol::Mul,
+                Expr::Operand(Operand::Num(19))
+            )
+        );
+    }
+
+    fn parse(expr: &str) -> Expr {
+        let (rest, expr) = parse_expr::<ErrType>(Span::new(expr)).unwrap();
+        assert!(rest.is_empty(), "{rest}");
+        expr
+    }
+
+    #[test]
+    fn test_expr() {
+        for (input, printed, value) in [
+            ("old * 19", "old * 19", 190),
+            ("old*old", "old * old", 100),
+            ("old + 2 * 3", "old + 2 * 3", 16),
+            ("(old + 2) * 3", "(old + 2) * 3", 36),
+            ("old - 2 - 3", "old - 2 - 3", 5),
+            ("old - (5 - 3 + 4)", "old - (5 - 3 + 4)", 4),
+            (
+                "( old / 3 ) * (old / (1 + 1))",
+                "old / 3 * (old / (1 + 1))",
+                15,
+            ),
+            ("((old))", "old", 10),
+        ] {
+            let expr = parse(input);
+            assert_eq!(expr.to_string(), printed);
+            assert_eq!(parse(printed), expr);
+            assert_eq!(expr.eval(10), value);
+        }
+
+        assert!(parse_expr::<ErrType>(Span::new("(old + 1")).is_err());
+        assert!(parse_expr::<ErrType>(Span::new("* 2")).is_err());
+    }
+
+    #[test]
+    fn test_eval_mod() {
+        let expr = parse("old * old - old + 3");
+        for old in [0, 5, 96577, 1 << 40] {
+            let old_wide = old as u128;
+            let expected = (old_wide * old_wide - old_wide + 3) % 96577;
+            assert_eq!(expr.eval_mod(old, 96577), Some(expected as usize));
+        }
+        assert_eq!(parse("old / 2").eval_mod(10, 3), None);
     }
 
     #[test]
@@ -398,5 +571,20 @@ mod test {
         let input = "Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old * 19\n  Test: divisible by 23\n    If true: throw to monkey 2\n    If false: throw to monkey 3\n";
         let actual = parse_monkey::<ErrType>(Span::new(input));
         assert!(actual.is_ok(), "{}", actual.err().unwrap());
+        let monkey = actual.unwrap().1;
+        assert_eq!(monkey.to_string() + "\n", input);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = include_str!("../../input/11.txt");
+        let monkeys: Result<_, ErrType> = final_parser(parse_monkeys::<ErrType>)(Span::new(input));
+        let monkeys: Vec<Monkey> = monkeys.unwrap();
+        assert_eq!(monkeys.iter().join("\n\n") + "\n", input);
+
+        let mut copy = monkeys.clone();
+        assert_eq!(copy, monkeys);
+        simulate_round_a(&mut copy);
+        assert_ne!(copy, monkeys);
     }
 }
@@ -17,6 +17,144 @@ use days::day13::*;
 mod days;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((command, args)) = args.split_first() {
+        match command.as_str() {
+            "day05-replay" => match (
+                args.first().map_or(Ok(500), |arg| arg.parse()),
+                args.get(1).map_or(Ok(0), |arg| arg.parse()),
+            ) {
+                (Ok(delay), Ok(start)) => {
+                    day05_replay(std::time::Duration::from_millis(delay), start)
+                }
+                _ => println!("Usage: day05-replay [delay in ms] [start step]"),
+            },
+            "day05-browse" => match args.first().map_or(Ok(0), |arg| arg.parse()) {
+                Ok(start) => day05_browse(start),
+                Err(_) => println!("Usage: day05-browse [start step]"),
+            },
+            "day05-dump" => match args.first() {
+                Some(path) => day05_dump(std::path::Path::new(path)),
+                None => println!("Usage: day05-dump <path>"),
+            },
+            "day05-final" => day05_final_arrangement(),
+            "day05-plan" => match (
+                args.first(),
+                args.get(1),
+                args.get(2).map_or(Ok(10), |arg| arg.parse()),
+            ) {
+                (Some(start), Some(goal), Ok(max_moves)) => day05_plan(
+                    std::path::Path::new(start),
+                    std::path::Path::new(goal),
+                    max_moves,
+                ),
+                _ => println!("Usage: day05-plan <start> <goal> [max moves]"),
+            },
+            "day06-bench" => match args.first().map_or(Ok(4_000_000), |arg| arg.parse()) {
+                Ok(size) => day06_bench(size),
+                Err(_) => println!("Usage: day06-bench [stream size in bytes]"),
+            },
+            "day06-stream" => {
+                let packet_len = args.first().map_or(Ok(PACKET_LEN), |arg| arg.parse());
+                let message_len = args.get(1).map_or(Ok(MESSAGE_LEN), |arg| arg.parse());
+                match (packet_len, message_len) {
+                    (Ok(packet_len), Ok(message_len)) if packet_len > 0 && message_len > 0 => {
+                        if let Err(e) = day06_stream(packet_len, message_len) {
+                            println!("{e}");
+                        }
+                    }
+                    _ => println!("Usage: day06-stream [packet length] [message length] < data"),
+                }
+            }
+            "day07" => day07_query(args),
+            "day07-export" => day07_export(args),
+            "day07-import" => match args.first() {
+                Some(path) => day07_import(std::path::Path::new(path)),
+                None => println!("Usage: day07-import <path to json>"),
+            },
+            "day07-plan" => match args
+                .iter()
+                .map(|arg| arg.parse())
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(sizes) if sizes.len() == 2 => day07_plan(sizes[0], sizes[1]),
+                _ => println!("Usage: day07-plan <disk size> <required space>"),
+            },
+            "day08-render" => {
+                let shading = args.first().map_or("scenic", String::as_str);
+                let format = args.get(1).map_or("ansi", String::as_str);
+                let path = args.get(2).map(std::path::Path::new);
+                if let Err(e) = day08_render(shading, format, path) {
+                    println!("{e}");
+                }
+            }
+            "day09-play" => {
+                let knots = args.first().map_or(Ok(10), |arg| arg.parse());
+                let delay = args.get(1).map_or(Ok(100), |arg| arg.parse());
+                let input = args.get(2).map(std::path::Path::new);
+                match (knots, delay) {
+                    (Ok(knots), Ok(delay)) => {
+                        day09_play(knots, std::time::Duration::from_millis(delay), input)
+                    }
+                    _ => println!("Usage: day09-play [knots] [delay in ms] [input]"),
+                }
+            }
+            "day09-dump" => match (args.first().map(|arg| arg.parse()), args.get(1)) {
+                (Some(Ok(knots)), Some(path)) => day09_dump(
+                    knots,
+                    std::path::Path::new(path),
+                    args.get(2).map(std::path::Path::new),
+                ),
+                _ => println!("Usage: day09-dump <knots> <path> [input]"),
+            },
+            "day10-debug" => match args.first() {
+                Some(program) => {
+                    let script = args.get(1).map(std::path::Path::new);
+                    if let Err(e) = day10_debug(std::path::Path::new(program), script) {
+                        println!("{e}");
+                    }
+                }
+                None => println!("Usage: day10-debug <program> [script]"),
+            },
+            "day10-render" => {
+                let format = args.first().map_or("text", String::as_str);
+                let path = args.get(1).map(std::path::Path::new);
+                if let Err(e) = day10_render(format, path) {
+                    println!("{e}");
+                }
+            }
+            "day10-asm" | "day10-disasm" => match args.first() {
+                Some(path) => {
+                    let path = std::path::Path::new(path);
+                    let result = match command.as_str() {
+                        "day10-asm" => day10_assemble(path),
+                        _ => day10_disassemble(path),
+                    };
+                    if let Err(e) = result {
+                        println!("{e}");
+                    }
+                }
+                None => println!("Usage: {command} <path>"),
+            },
+            "day10-synth" => {
+                let result = match args {
+                    [flag, path] if flag == "--image" => {
+                        day10_synthesize_image(std::path::Path::new(path))
+                    }
+                    [text] => day10_synthesize_text(text),
+                    _ => Err(anyhow::anyhow!(
+                        "Usage: day10-synth <TEXT> or day10-synth --image <path>"
+                    )),
+                };
+                if let Err(e) = result {
+                    println!("{e}");
+                }
+            }
+            _ => println!("Unknown command {command}"),
+        }
+        return;
+    }
+
     // day01();
     // day02();
     // day03();